@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use minigrep::search::{MatchOptions, Matcher};
+
+const ASCII: &[&str] = &[
+    "2024-05-01T12:00:00Z INFO request served in 12ms",
+    "2024-05-01T12:00:03Z ERROR Timeout while talking to STRASSE-7",
+];
+
+const MIXED: &[&str] = &[
+    "2024-05-01T12:00:01Z WARN retrying connection to straße-7",
+    "2024-05-01T12:00:02Z INFO ΣΟΦΟΣ cache refreshed",
+];
+
+fn haystack(lines: &[&str]) -> String {
+    lines.iter().cycle().take(20_000).map(|line| format!("{}\n", line)).collect()
+}
+
+/// The implementation this benchmark started from: lowercase every line.
+fn search_to_lowercase<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query_lower = query.to_lowercase();
+    let mut v = Vec::new();
+    for line in contents.lines() {
+        if line.to_lowercase().contains(&query_lower) {
+            v.push(line);
+        }
+    }
+    v
+}
+
+/// The same loop as [`search_to_lowercase`], so only the matching differs.
+fn search_matcher<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|line| matcher.is_match(line)).collect()
+}
+
+fn case_insensitive(c: &mut Criterion) {
+    let query = "strasse-7";
+    let ignore_case = MatchOptions {
+        ignore_case: true,
+        ..MatchOptions::default()
+    };
+    let folded = Matcher::new(query, ignore_case).unwrap();
+    let regex = Matcher::new(&regex::escape(query), MatchOptions { regex: true, ..ignore_case }).unwrap();
+
+    for (name, lines) in [("ascii", ASCII), ("mixed", MIXED)] {
+        let contents = haystack(lines);
+        let mut group = c.benchmark_group(format!("case_insensitive/{}", name));
+        group.bench_function("to_lowercase", |b| b.iter(|| search_to_lowercase(black_box(query), &contents)));
+        group.bench_function("regex", |b| b.iter(|| search_matcher(black_box(&regex), &contents)));
+        group.bench_function("full_case_folding", |b| b.iter(|| search_matcher(black_box(&folded), &contents)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, case_insensitive);
+criterion_main!(benches);
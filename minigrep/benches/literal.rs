@@ -0,0 +1,57 @@
+use std::io::Cursor;
+use std::ops::ControlFlow;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use minigrep::search::{self, Event, MatchOptions, Matcher, SearchOptions};
+
+const LINES: &[&str] = &[
+    "2024-05-01T12:00:00Z INFO request served in 12ms",
+    "2024-05-01T12:00:01Z DEBUG cache hit for /api/v1/users",
+    "2024-05-01T12:00:02Z INFO request served in 8ms",
+    "2024-05-01T12:00:03Z WARN slow query on orders table",
+];
+
+/// A large input in which the queries below are rare.
+fn haystack() -> String {
+    let mut contents: String = LINES.iter().cycle().take(200_000).map(|line| format!("{}\n", line)).collect();
+    contents.push_str("2024-05-01T12:00:04Z ERROR connection reset by peer\n");
+    contents
+}
+
+/// Where literal search started from: `str::contains` on every line.
+fn count_contains(queries: &[&str], contents: &str) -> usize {
+    contents.lines().filter(|line| queries.iter().any(|query| line.contains(query))).count()
+}
+
+fn count_reader(matcher: &Matcher, contents: &str) -> usize {
+    let mut count = 0;
+    search::search_reader(matcher, &SearchOptions::default(), Cursor::new(contents), |event| {
+        if let Event::Match(_) = event {
+            count += 1;
+        }
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    count
+}
+
+fn literal(c: &mut Criterion) {
+    let contents = haystack();
+    let regex = MatchOptions { regex: true, ..MatchOptions::default() };
+
+    for (name, queries) in [("one", &["connection reset"][..]), ("several", &["connection reset", "deadlock", "panicked at", "out of memory"][..])] {
+        let literal = Matcher::any(queries, MatchOptions::default()).unwrap();
+        let escaped: Vec<String> = queries.iter().map(|query| regex::escape(query)).collect();
+        let alternation = Matcher::any(&escaped, regex).unwrap();
+
+        let mut group = c.benchmark_group(format!("literal/{}", name));
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function("lines_contains", |b| b.iter(|| count_contains(black_box(queries), &contents)));
+        group.bench_function("regex_line_by_line", |b| b.iter(|| count_reader(black_box(&alternation), &contents)));
+        group.bench_function("whole_buffer", |b| b.iter(|| count_reader(black_box(&literal), &contents)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, literal);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use minigrep::io::{self, MmapChoice};
+use minigrep::search::{self, Event, MatchOptions, Matcher, SearchOptions};
+
+const LINES: &[&str] = &[
+    "2024-05-01T12:00:00Z INFO request served in 12ms",
+    "2024-05-01T12:00:01Z DEBUG cache hit for /api/v1/users",
+    "2024-05-01T12:00:02Z INFO request served in 8ms",
+    "2024-05-01T12:00:03Z WARN slow query on orders table",
+];
+
+/// File sizes on both sides of `io::MMAP_THRESHOLD`.
+const SIZES: &[usize] = &[4 << 10, 64 << 10, 1 << 20, 32 << 20];
+
+fn write_file(path: &Path, size: usize) {
+    let mut contents = String::with_capacity(size);
+    for line in LINES.iter().cycle() {
+        if contents.len() + line.len() >= size {
+            break;
+        }
+        contents.push_str(line);
+        contents.push('\n');
+    }
+    fs::write(path, contents).unwrap();
+}
+
+fn counter(count: &mut usize) -> impl FnMut(Event) -> ControlFlow<()> + '_ {
+    |event| {
+        if let Event::Match(_) = event {
+            *count += 1;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Both modes open the file on every iteration, as minigrep does, so the
+/// cost of setting up a mapping is part of what is measured.
+fn count_read(matcher: &Matcher, path: &Path) -> usize {
+    let mut count = 0;
+    let reader = BufReader::new(File::open(path).unwrap());
+    search::search_reader(matcher, &SearchOptions::default(), reader, counter(&mut count)).unwrap();
+    count
+}
+
+fn count_mmap(matcher: &Matcher, path: &Path) -> usize {
+    let mut count = 0;
+    let map = io::map(path, MmapChoice::Always).unwrap().unwrap();
+    search::search_slice(matcher, &SearchOptions::default(), &map, counter(&mut count)).unwrap();
+    count
+}
+
+/// The query never matches, so input handling is most of the work.
+fn mmap(c: &mut Criterion) {
+    let matcher = Matcher::new("ERROR", MatchOptions::default()).unwrap();
+    let dir = std::env::temp_dir().join(format!("minigrep-bench-mmap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut group = c.benchmark_group("mmap");
+    for &size in SIZES {
+        let path = dir.join(size.to_string());
+        write_file(&path, size);
+        assert_eq!(count_read(&matcher, &path), count_mmap(&matcher, &path));
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("read", size), &path, |b, path| b.iter(|| count_read(&matcher, path)));
+        group.bench_with_input(BenchmarkId::new("mmap", size), &path, |b, path| b.iter(|| count_mmap(&matcher, path)));
+    }
+    group.finish();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+criterion_group!(benches, mmap);
+criterion_main!(benches);
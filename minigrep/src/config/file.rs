@@ -0,0 +1,153 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where a setting came from, as `--debug-config` reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Environment(&'static str),
+    /// The flags at the top of a config file, which apply to every run.
+    File(PathBuf),
+    /// A named section of a config file, applied with `--profile NAME`.
+    Profile(String, PathBuf),
+    CommandLine,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Environment(name) => write!(f, "environment variable {}", name),
+            Source::File(path) => write!(f, "config file {}", path.display()),
+            Source::Profile(name, path) => write!(f, "profile '{}' in {}", name, path.display()),
+            Source::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// Default flags, and named profiles of more flags, read from a file.
+///
+/// Each line holds one argument, exactly as it would reach minigrep from
+/// the command line, with no shell quoting and no spaces trimmed, so
+/// `--glob=*.rs` or `-e` followed by a line with the pattern. Blank lines
+/// and lines starting with `#` are skipped, and a line `[NAME]` starts the
+/// profile NAME:
+///
+/// ```text
+/// # every search
+/// --line-number
+///
+/// [rust-errors]
+/// --type=rust
+/// --pattern=error
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub defaults: Vec<String>,
+    profiles: Vec<(String, Vec<String>)>,
+}
+
+impl ConfigFile {
+    /// Reads the config file at [`ConfigFile::location`], if there is one.
+    /// Only a file that `MINIGREP_CONFIG` names has to exist.
+    pub fn load() -> Result<Option<ConfigFile>, String> {
+        let (path, required) = match ConfigFile::location(|name| env::var_os(name)) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => ConfigFile::parse(&path, &text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(None),
+            Err(e) => Err(format!("cannot read config file {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Where the config file is, given a way to read environment variables,
+    /// and whether it has to exist: the path in `MINIGREP_CONFIG`, where an
+    /// empty one means none, or else `minigrep/config` in the XDG config
+    /// directory, `$XDG_CONFIG_HOME` or `~/.config`.
+    pub fn location(var: impl Fn(&str) -> Option<OsString>) -> Option<(PathBuf, bool)> {
+        if let Some(path) = var("MINIGREP_CONFIG") {
+            return (!path.is_empty()).then(|| (PathBuf::from(path), true));
+        }
+        let dir = match var("XDG_CONFIG_HOME").map(PathBuf::from) {
+            // a relative path is to be ignored, says the XDG spec
+            Some(dir) if dir.is_absolute() => dir,
+            _ => Path::new(&var("HOME")?).join(".config"),
+        };
+        Some((dir.join("minigrep").join("config"), false))
+    }
+
+    pub fn parse(path: &Path, text: &str) -> Result<ConfigFile, String> {
+        let mut file = ConfigFile {
+            path: path.to_path_buf(),
+            ..ConfigFile::default()
+        };
+        for (index, line) in text.lines().enumerate() {
+            // an argument is kept as written, spaces and all, since it may
+            // be the value of the flag before it
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let Some(name) = trimmed.strip_prefix('[').and_then(|line| line.strip_suffix(']')) else {
+                match file.profiles.last_mut() {
+                    Some((_, args)) => args.push(line.to_string()),
+                    None => file.defaults.push(line.to_string()),
+                }
+                continue;
+            };
+            let name = name.trim();
+            if name.is_empty() || file.profile(name).is_some() {
+                let problem = if name.is_empty() { "a profile needs a name" } else { "profile defined twice" };
+                return Err(format!("{}:{}: {}", path.display(), index + 1, problem));
+            }
+            file.profiles.push((name.to_string(), Vec::new()));
+        }
+        Ok(file)
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&[String]> {
+        self.profiles
+            .iter()
+            .find(|(profile, _)| profile == name)
+            .map(|(_, args)| args.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_come_before_the_first_profile() {
+        let text = "  # comment\n--line-number\n  \n[rust-errors]\n--type=rust\n-e\n error \n[ logs ]\n";
+        let file = ConfigFile::parse(Path::new("config"), text).unwrap();
+        assert_eq!(vec!["--line-number"], file.defaults);
+        // values keep their spaces
+        let rust_errors = ["--type=rust".to_string(), "-e".to_string(), " error ".to_string()];
+        assert_eq!(Some(&rust_errors[..]), file.profile("rust-errors"));
+        assert_eq!(Some(&[][..]), file.profile("logs"));
+        assert_eq!(None, file.profile("nope"));
+
+        let err = ConfigFile::parse(Path::new("config"), "[a]\n[b]\n[a]\n").unwrap_err();
+        assert_eq!("config:3: profile defined twice", err);
+    }
+
+    #[test]
+    fn the_location_follows_xdg() {
+        let location = |vars: &[(&str, &str)]| {
+            ConfigFile::location(|name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.into()))
+        };
+        assert_eq!(Some((PathBuf::from("my.conf"), true)), location(&[("MINIGREP_CONFIG", "my.conf")]));
+        assert_eq!(None, location(&[("MINIGREP_CONFIG", ""), ("HOME", "/home/me")]));
+        let xdg = location(&[("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/me")]);
+        assert_eq!(Some((PathBuf::from("/xdg/minigrep/config"), false)), xdg);
+        let home = location(&[("XDG_CONFIG_HOME", "xdg"), ("HOME", "/home/me")]);
+        assert_eq!(Some((PathBuf::from("/home/me/.config/minigrep/config"), false)), home);
+        assert_eq!(None, location(&[]));
+    }
+}
@@ -0,0 +1,780 @@
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use encoding_rs::Encoding;
+
+use crate::error::Error;
+use crate::io::{MmapChoice, STDIN};
+use crate::output::ColorChoice;
+use crate::search::{MatchOptions, SearchOptions};
+use crate::walk::WalkOptions;
+
+mod file;
+
+pub use file::{ConfigFile, Source};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] PATTERN [PATH...]
+       minigrep [OPTIONS] -e PATTERN... [-f FILE...] [PATH...]
+       minigrep index build [OPTIONS] DIR...
+
+Each PATH may be a file or a directory, which is searched recursively.
+With no PATH, or when PATH is -, standard input is read. With -e or -f a
+line is selected when it matches any of the patterns.
+
+`minigrep index build DIR` records the trigrams of every file below DIR in
+DIR/.minigrep-index, rereading only the files that changed since the last
+build; with --index, searches of DIR then skip the files it rules out.
+
+Defaults for every search are read from the file $MINIGREP_CONFIG names,
+or else from $XDG_CONFIG_HOME/minigrep/config (~/.config/minigrep/config),
+which holds one argument per line, e.g. --line-number or --glob=*.rs. A
+line [NAME] starts a profile: flags that only apply with --profile NAME.
+Blank lines and lines starting with # are skipped. Flags given later win:
+the command line over the profile, and the profile over the defaults. A
+flag that switches something on, such as --line-number, is switched off
+again by its --no- form, --no-line-number; --ignore undoes --no-ignore.
+
+The exit status is 0 when a line is selected, 1 when none is, and 2 when
+an error occurred.
+
+Options:
+  -e, --pattern PATTERN      search for PATTERN (repeatable)
+  -f, --file FILE            read patterns from FILE, one per line (repeatable)
+      --show-pattern         print the pattern that matched in front of each
+                             matching line
+  -i, --ignore-case          ignore case distinctions (also set by IGNORE_CASE)
+  -v, --invert-match         select non-matching lines
+  -w, --word-regexp          only match whole words: the characters around a
+                             match may not be letters, digits or _
+  -x, --line-regexp          only match whole lines
+  -U, --multiline            let matches span lines, e.g. with \n in a regular
+                             expression, where ^ and $ match at every line;
+                             each region is printed whole, after the numbers
+                             of its first and last lines
+      --fuzzy K              select lines with a substring at most K edits
+                             (inserted, deleted or replaced characters) away
+                             from a pattern, and highlight the closest one
+  -n, --line-number          prefix each line with its line number
+  -b, --byte-offset          prefix each line with its byte offset in the input
+  -A, --after-context N      print N lines of context after each match
+  -B, --before-context N     print N lines of context before each match
+  -C, --context N            print N lines of context around each match
+  -c, --count                print only a count of matching lines
+  -l, --files-with-matches   print only the name of a file with matches
+  -L, --files-without-match  print only the name of a file without matches
+  -m, --max-count N          stop reading a file after N selected lines
+  -r, --replace TEMPLATE     print matching lines with every match replaced by
+                             TEMPLATE, where $0 is the match and $1, $name
+                             are capture groups of a regular expression
+      --write                with --replace, rewrite the files in place
+                             instead, keeping the originals as FILE.bak
+      --regex                interpret PATTERN as a regular expression
+  -a, --text                 search binary files (with a NUL byte early on)
+                             as text instead of only reporting a match
+      --encoding ENCODING    decode input as ENCODING, e.g. utf-16le or
+                             latin1, unless it starts with a byte order mark;
+                             by default it is UTF-8, or what its BOM says
+      --mmap                 map every regular file into memory to search it,
+                             rather than only large ones
+      --no-mmap              never map files into memory; read them instead
+  -z, --search-zip           search inside gzip, bzip2, xz and zstd files,
+                             told apart by their contents or extension
+      --color[=WHEN]         highlight matches: always, never or auto (the
+                             default); colors are read from MINIGREP_COLORS
+      --json                 print results as JSON Lines: one record per
+                             matching or context line, then a summary
+      --hidden               search hidden files and directories
+      --no-ignore            do not respect .gitignore, .ignore and git's
+                             exclude files
+  -g, --glob GLOB            only search files matching GLOB; a leading !
+                             excludes them instead (repeatable)
+  -t, --type TYPE            only search files of TYPE, e.g. rust (repeatable)
+  -T, --type-not TYPE        do not search files of TYPE (repeatable)
+      --follow               keep searching what is appended to each file, as
+                             tail -F does, across truncation and rotation
+      --index                skip the files the index of each directory PATH
+                             shows cannot match (see index build above)
+  -j, --threads N            search up to N files at once (default: one per
+                             CPU); output keeps the order of the files
+      --profile NAME         also apply the flags of profile NAME in the
+                             config file
+      --debug-config         print which config file, profile, environment
+                             variable or command line set each option
+  -h, --help                 print this help and exit
+  -V, --version              print version information and exit
+  --                         treat every following argument as positional";
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Given with `-e`, or else the first positional argument.
+    pub patterns: Vec<String>,
+    /// Files to read more patterns from, one per line.
+    pub pattern_files: Vec<String>,
+    pub show_pattern: bool,
+    pub paths: Vec<String>,
+    pub ignore_case: bool,
+    pub regex: bool,
+    pub text: bool,
+    pub encoding: Option<&'static Encoding>,
+    pub search_zip: bool,
+    pub mmap: MmapChoice,
+    pub invert_match: bool,
+    pub whole_word: bool,
+    pub whole_line: bool,
+    pub multiline: bool,
+    /// `--fuzzy K`: how many edits a match may be away from a pattern.
+    pub fuzzy: Option<usize>,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub count: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub max_count: Option<usize>,
+    pub replace: Option<String>,
+    pub write: bool,
+    pub color: ColorChoice,
+    pub json: bool,
+    pub threads: Option<usize>,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    pub globs: Vec<String>,
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    /// `index build`: index the directories in `paths` rather than search.
+    pub build_index: bool,
+    pub index: bool,
+    pub follow: bool,
+    pub profile: Option<String>,
+    /// The config file that was read, if any.
+    pub config_file: Option<PathBuf>,
+    /// Every flag that was applied, in order, and where it came from.
+    pub settings: Vec<Setting>,
+    pub debug_config: bool,
+    pub show_help: bool,
+    pub show_version: bool,
+}
+
+impl Config {
+    pub fn new(args: &[String]) -> Result<Config, Error> {
+        // the program name is not an argument
+        let args = args.get(1..).unwrap_or_default();
+        Config::load(args, ConfigFile::load(), env::var("IGNORE_CASE").is_ok()).map_err(Error::Args)
+    }
+
+    /// [`Config::parse`] with the config file as it was read, whose errors
+    /// do not stand in the way of `--help` and `--version`.
+    fn load(args: &[String], file: Result<Option<ConfigFile>, String>, ignore_case: bool) -> Result<Config, String> {
+        let config = file.and_then(|file| Config::parse(args, file.as_ref(), ignore_case));
+        config.or_else(|err| match Config::parse(args, None, ignore_case) {
+            Ok(config) if config.show_help || config.show_version => Ok(config),
+            _ => Err(err),
+        })
+    }
+
+    /// Applies, in this order so that later flags win, `IGNORE_CASE`, the
+    /// defaults in `file`, the profile `--profile` names and `args`.
+    fn parse(args: &[String], file: Option<&ConfigFile>, env_ignore_case: bool) -> Result<Config, String> {
+        let mut config = Config::default();
+        if env_ignore_case {
+            config.set_from("ignore-case", None, &Source::Environment("IGNORE_CASE"))?;
+        }
+        let args = match args {
+            [index, build, rest @ ..] if index == "index" && build == "build" => {
+                config.build_index = true;
+                rest
+            }
+            _ => args,
+        };
+
+        // the profile has to be applied before the command line that names it
+        let mut command_line = Config::default();
+        command_line.apply(args, &Source::CommandLine)?;
+        if let Some(file) = file {
+            config.config_file = Some(file.path.clone());
+            config.apply_flags(&file.defaults, Source::File(file.path.clone()))?;
+        }
+        if let Some(name) = &command_line.profile {
+            let file = file.ok_or_else(|| format!("no config file to read profile '{}' from", name))?;
+            let profile = file
+                .profile(name)
+                .ok_or_else(|| format!("unknown profile '{}' in {}", name, file.path.display()))?;
+            config.apply_flags(profile, Source::Profile(name.clone(), file.path.clone()))?;
+        }
+        let positional = config.apply(args, &Source::CommandLine)?;
+
+        if config.show_help || config.show_version {
+            return Ok(config);
+        }
+        if config.json && (config.count || config.files_with_matches || config.files_without_match) {
+            return Err("--json cannot be combined with -c, -l or -L".to_string());
+        }
+        if config.json && config.replace.is_some() {
+            return Err("--json cannot be combined with --replace".to_string());
+        }
+        if config.follow && (config.count || config.files_with_matches || config.files_without_match || config.write) {
+            return Err("--follow cannot be combined with -c, -l, -L or --write".to_string());
+        }
+        let context = config.before_context > 0 || config.after_context > 0;
+        if config.multiline && (config.invert_match || context || config.json || config.write || config.follow) {
+            return Err("--multiline cannot be combined with -v, -A, -B, -C, --json, --write or --follow".to_string());
+        }
+        if config.fuzzy.is_some() && (config.regex || config.whole_word || config.whole_line || config.multiline) {
+            return Err("--fuzzy cannot be combined with --regex, -w, -x or --multiline".to_string());
+        }
+        if config.write && config.replace.is_none() {
+            return Err("--write needs --replace".to_string());
+        }
+
+        if config.build_index {
+            if positional.is_empty() {
+                return Err("not enough arguments: missing DIR".to_string());
+            }
+            config.paths = positional;
+            return Ok(config);
+        }
+
+        let mut positional = positional.into_iter();
+        if config.patterns.is_empty() && config.pattern_files.is_empty() {
+            let pattern = positional.next().ok_or("not enough arguments: missing PATTERN")?;
+            config.patterns.push(pattern);
+        }
+        config.paths = positional.collect();
+        if config.paths.is_empty() {
+            config.paths.push(STDIN.to_string());
+        }
+
+        Ok(config)
+    }
+
+    /// Applies the flags in `args` and returns the other arguments.
+    fn apply(&mut self, args: &[String], source: &Source) -> Result<Vec<String>, String> {
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args.by_ref().cloned());
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let value = if takes_value(name) {
+                    let value = inline.or_else(|| args.next().cloned());
+                    Some(value.ok_or_else(|| format!("flag '--{}' needs a value", name))?)
+                } else if let Some(default) = default_value(name) {
+                    Some(inline.unwrap_or_else(|| default.to_string()))
+                } else if inline.is_some() {
+                    return Err(format!("flag '--{}' does not take a value", name));
+                } else {
+                    None
+                };
+                self.set_from(name, value.as_deref(), source)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // short flags may be bundled, e.g. `-in`, and a flag taking a
+                // value swallows the rest of the bundle, e.g. `-nC2`
+                for (index, short) in arg.char_indices().skip(1) {
+                    let name = short_name(short)
+                        .ok_or_else(|| format!("unknown flag '-{}' (see 'minigrep --help')", short))?;
+                    if !takes_value(name) {
+                        self.set_from(name, None, source)?;
+                        continue;
+                    }
+                    let rest = &arg[index + short.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next().cloned().ok_or_else(|| format!("flag '-{}' needs a value", short))?
+                    } else {
+                        rest.to_string()
+                    };
+                    self.set_from(name, Some(&value), source)?;
+                    break;
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(positional)
+    }
+
+    /// Applies `args` from a config file, which can only hold flags.
+    fn apply_flags(&mut self, args: &[String], source: Source) -> Result<(), String> {
+        let positional = self.apply(args, &source).map_err(|e| format!("{}: {}", source, e))?;
+        if let Some(arg) = positional.first() {
+            return Err(format!("{}: unexpected argument '{}': only flags can be set there", source, arg));
+        }
+        if self.profile.is_some() {
+            return Err(format!("{}: --profile can only be given on the command line", source));
+        }
+        Ok(())
+    }
+
+    fn set_from(&mut self, flag: &str, value: Option<&str>, source: &Source) -> Result<(), String> {
+        self.set(flag, value)?;
+        self.settings.push(Setting {
+            flag: flag.to_string(),
+            value: value.map(str::to_string),
+            source: source.clone(),
+        });
+        Ok(())
+    }
+
+    fn set(&mut self, flag: &str, value: Option<&str>) -> Result<(), String> {
+        if value.is_none() {
+            if let Some(switch) = self.switch(flag) {
+                *switch = true;
+                return Ok(());
+            }
+            // every switch can be turned off again, e.g. one a config file set
+            if let Some(switch) = flag.strip_prefix("no-").and_then(|flag| self.switch(flag)) {
+                *switch = false;
+                return Ok(());
+            }
+        }
+        match (flag, value) {
+            ("ignore", None) => self.no_ignore = false,
+            ("encoding", Some(value)) => self.encoding = parse_encoding(value)?,
+            ("mmap", None) => self.mmap = MmapChoice::Always,
+            ("no-mmap", None) => self.mmap = MmapChoice::Never,
+            ("replace", Some(value)) => self.replace = Some(value.to_string()),
+            ("profile", Some(value)) => self.profile = Some(value.to_string()),
+            ("pattern", Some(value)) => self.patterns.push(value.to_string()),
+            ("file", Some(value)) => self.pattern_files.push(value.to_string()),
+            ("glob", Some(value)) => self.globs.push(value.to_string()),
+            ("type", Some(value)) => self.types.push(value.to_string()),
+            ("type-not", Some(value)) => self.types_not.push(value.to_string()),
+            ("help", None) => self.show_help = true,
+            ("version", None) => self.show_version = true,
+            ("after-context", Some(value)) => self.after_context = parse_number(flag, value)?,
+            ("before-context", Some(value)) => self.before_context = parse_number(flag, value)?,
+            ("color", Some(value)) => self.color = value.parse()?,
+            ("threads", Some(value)) => self.threads = Some(parse_number(flag, value)?),
+            ("max-count", Some(value)) => self.max_count = Some(parse_number(flag, value)?),
+            ("fuzzy", Some(value)) => self.fuzzy = Some(parse_number(flag, value)?),
+            ("context", Some(value)) => {
+                self.after_context = parse_number(flag, value)?;
+                self.before_context = self.after_context;
+            }
+            _ => return Err(format!("unknown flag '--{}' (see 'minigrep --help')", flag)),
+        }
+        Ok(())
+    }
+
+    /// The field behind a flag that switches something on, which the same
+    /// flag with `no-` in front switches off.
+    fn switch(&mut self, flag: &str) -> Option<&mut bool> {
+        let switch = match flag {
+            "ignore-case" => &mut self.ignore_case,
+            "invert-match" => &mut self.invert_match,
+            "word-regexp" => &mut self.whole_word,
+            "line-regexp" => &mut self.whole_line,
+            "multiline" => &mut self.multiline,
+            "line-number" => &mut self.line_number,
+            "byte-offset" => &mut self.byte_offset,
+            "count" => &mut self.count,
+            "files-with-matches" => &mut self.files_with_matches,
+            "files-without-match" => &mut self.files_without_match,
+            "regex" => &mut self.regex,
+            "text" => &mut self.text,
+            "search-zip" => &mut self.search_zip,
+            "json" => &mut self.json,
+            "write" => &mut self.write,
+            "hidden" => &mut self.hidden,
+            "no-ignore" => &mut self.no_ignore,
+            "index" => &mut self.index,
+            "follow" => &mut self.follow,
+            "show-pattern" => &mut self.show_pattern,
+            "debug-config" => &mut self.debug_config,
+            _ => return None,
+        };
+        Some(switch)
+    }
+
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            regex: self.regex,
+            ignore_case: self.ignore_case,
+            whole_word: self.whole_word,
+            whole_line: self.whole_line,
+            multiline: self.multiline,
+            fuzzy: self.fuzzy,
+        }
+    }
+
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            hidden: self.hidden,
+            no_ignore: self.no_ignore,
+            globs: self.globs.clone(),
+            types: self.types.clone(),
+            types_not: self.types_not.clone(),
+        }
+    }
+
+    pub fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            invert: self.invert_match,
+            before_context: self.before_context,
+            after_context: self.after_context,
+            max_count: self.max_count,
+        }
+    }
+
+    /// [`Config::settings`] grouped by option, in the order each was first
+    /// set. Of an option that cannot be repeated, the last setting counts.
+    pub fn settings_by_option(&self) -> Vec<&Setting> {
+        let mut settings: Vec<&Setting> = self.settings.iter().collect();
+        // a switch and its `no-` form are one option
+        let option = |flag: &str| flag.strip_prefix("no-").unwrap_or(flag).to_string();
+        let first = |flag: &str| self.settings.iter().position(|setting| option(&setting.flag) == option(flag));
+        settings.sort_by_key(|setting| first(&setting.flag));
+        settings
+    }
+}
+
+/// A flag applied to a [`Config`], with its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    /// The long name of the flag, without `--`.
+    pub flag: String,
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+impl Display for Setting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "--{}={} from {}", self.flag, value, self.source),
+            None => write!(f, "--{} from {}", self.flag, self.source),
+        }
+    }
+}
+
+fn short_name(flag: char) -> Option<&'static str> {
+    let name = match flag {
+        'i' => "ignore-case",
+        'e' => "pattern",
+        'f' => "file",
+        'v' => "invert-match",
+        'w' => "word-regexp",
+        'x' => "line-regexp",
+        'U' => "multiline",
+        'n' => "line-number",
+        'a' => "text",
+        'z' => "search-zip",
+        'b' => "byte-offset",
+        'c' => "count",
+        'l' => "files-with-matches",
+        'L' => "files-without-match",
+        'm' => "max-count",
+        'r' => "replace",
+        'j' => "threads",
+        'g' => "glob",
+        't' => "type",
+        'T' => "type-not",
+        'A' => "after-context",
+        'B' => "before-context",
+        'C' => "context",
+        'h' => "help",
+        'V' => "version",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn takes_value(flag: &str) -> bool {
+    matches!(
+        flag,
+        "after-context"
+            | "before-context"
+            | "context"
+            | "max-count"
+            | "fuzzy"
+            | "replace"
+            | "encoding"
+            | "threads"
+            | "pattern"
+            | "file"
+            | "glob"
+            | "type"
+            | "type-not"
+            | "profile"
+    )
+}
+
+/// Flags whose value is optional and, when given, must be attached with `=`.
+fn default_value(flag: &str) -> Option<&'static str> {
+    match flag {
+        "color" => Some("auto"),
+        _ => None,
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '--{}': expected a number", value, flag))
+}
+
+/// `auto` stands for the default: UTF-8 unless a byte order mark says
+/// otherwise.
+fn parse_encoding(value: &str) -> Result<Option<&'static Encoding>, String> {
+    if value == "auto" {
+        return Ok(None);
+    }
+    Encoding::for_label(value.as_bytes())
+        .map(Some)
+        .ok_or_else(|| format!("invalid value '{}' for '--encoding': unknown encoding", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Config::parse(&args, None, false)
+    }
+
+    #[test]
+    fn positional_arguments() {
+        let config = parse(&["to", "poem.txt"]).unwrap();
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert!(!config.ignore_case);
+    }
+
+    #[test]
+    fn several_paths() {
+        let config = parse(&["to", "poem.txt", "src"]).unwrap();
+        assert_eq!(vec!["poem.txt", "src"], config.paths);
+    }
+
+    #[test]
+    fn missing_path_reads_stdin() {
+        assert_eq!(vec![STDIN], parse(&["to"]).unwrap().paths);
+    }
+
+    #[test]
+    fn short_flags_can_be_bundled() {
+        let config = parse(&["-inv", "to", "poem.txt"]).unwrap();
+        assert!(config.ignore_case && config.line_number && config.invert_match);
+        assert!(!config.whole_word && !config.whole_line);
+
+        let config = parse(&["-wx", "to"]).unwrap();
+        assert!(config.whole_word && config.whole_line);
+        assert!(!config.count && !config.files_with_matches);
+    }
+
+    #[test]
+    fn double_dash_ends_flags() {
+        let config = parse(&["-c", "--", "-v", "poem.txt"]).unwrap();
+        assert_eq!(vec!["-v"], config.patterns);
+        assert!(config.count && !config.invert_match);
+    }
+
+    #[test]
+    fn env_var_is_a_fallback() {
+        let args = vec!["to".to_string(), "poem.txt".to_string()];
+        assert!(Config::parse(&args, None, true).unwrap().ignore_case);
+    }
+
+    #[test]
+    fn help_does_not_need_positional_arguments() {
+        assert!(parse(&["--help"]).unwrap().show_help);
+        assert!(parse(&["-V"]).unwrap().show_version);
+    }
+
+    #[test]
+    fn flags_with_values() {
+        let config = parse(&["-A", "2", "-B1", "to", "poem.txt"]).unwrap();
+        assert_eq!((1, 2), (config.before_context, config.after_context));
+
+        let config = parse(&["-nC3", "--after-context=1", "to"]).unwrap();
+        assert!(config.line_number);
+        assert_eq!((3, 1), (config.before_context, config.after_context));
+
+        let config = parse(&["--context", "4", "to"]).unwrap();
+        assert_eq!((4, 4), (config.before_context, config.after_context));
+
+        let config = parse(&["--color", "to"]).unwrap();
+        assert_eq!(ColorChoice::Auto, config.color);
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(ColorChoice::Never, parse(&["--color=never", "to"]).unwrap().color);
+
+        assert_eq!(Some(2), parse(&["-j2", "to"]).unwrap().threads);
+
+        let config = parse(&["-g", "*.rs", "--glob=!target/**", "-trust", "-Tmd", "to"]).unwrap();
+        assert_eq!(vec!["*.rs", "!target/**"], config.globs);
+        assert_eq!((vec!["rust".to_string()], vec!["md".to_string()]), (config.types, config.types_not));
+
+        let config = parse(&["-r", "$1", "--write", "(t)o"]).unwrap();
+        assert_eq!((Some("$1".to_string()), true), (config.replace, config.write));
+
+        assert!(parse(&["-Ui", "to"]).unwrap().match_options().multiline);
+        assert_eq!(Some(2), parse(&["--fuzzy", "2", "to"]).unwrap().match_options().fuzzy);
+
+        let config = parse(&["-az", "--encoding", "UTF-16LE", "to"]).unwrap();
+        assert!(config.text && config.search_zip);
+        assert_eq!(Some(encoding_rs::UTF_16LE), config.encoding);
+        assert_eq!(None, parse(&["--encoding=auto", "to"]).unwrap().encoding);
+
+        assert_eq!(MmapChoice::Auto, parse(&["to"]).unwrap().mmap);
+        assert_eq!(MmapChoice::Never, parse(&["--mmap", "--no-mmap", "to"]).unwrap().mmap);
+
+        let config = parse(&["-Lm", "5", "to"]).unwrap();
+        assert!(config.files_without_match);
+        assert_eq!(Some(5), config.max_count);
+    }
+
+    #[test]
+    fn several_patterns() {
+        let config = parse(&["-e", "fn", "--pattern=struct", "-fnames.txt", "src"]).unwrap();
+        assert_eq!(vec!["fn", "struct"], config.patterns);
+        assert_eq!(vec!["names.txt"], config.pattern_files);
+        assert_eq!(vec!["src"], config.paths);
+
+        // a pattern file alone is enough, and the first argument is a path
+        let config = parse(&["-f", "names.txt", "--show-pattern", "src"]).unwrap();
+        assert!(config.patterns.is_empty() && config.show_pattern);
+        assert_eq!(vec!["src"], config.paths);
+    }
+
+    #[test]
+    fn config_files_set_defaults_and_profiles() {
+        let text = "--line-number\n--max-count=5\n[rust-errors]\n--type=rust\n-e\nerror\n-m3\n[bad]\nsrc\n";
+        let file = ConfigFile::parse(Path::new("config"), text).unwrap();
+        let with_file = |args: &[&str], ignore_case: bool| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            Config::parse(&args, Some(&file), ignore_case)
+        };
+
+        let config = with_file(&["to"], false).unwrap();
+        assert!(config.line_number);
+        assert_eq!((Some(5), vec!["to".to_string()]), (config.max_count, config.patterns));
+
+        // the profile's pattern leaves the first argument to be a path
+        let config = with_file(&["-m", "7", "--profile", "rust-errors", "src"], true).unwrap();
+        assert_eq!((&["error".to_string()][..], &["src".to_string()][..]), (&config.patterns[..], &config.paths[..]));
+        assert_eq!((Some(7), &["rust".to_string()][..]), (config.max_count, &config.types[..]));
+        let sources: Vec<String> = config.settings_by_option().iter().map(|setting| setting.to_string()).collect();
+        assert_eq!(
+            vec![
+                "--ignore-case from environment variable IGNORE_CASE",
+                "--line-number from config file config",
+                "--max-count=5 from config file config",
+                "--max-count=3 from profile 'rust-errors' in config",
+                "--max-count=7 from command line",
+                "--type=rust from profile 'rust-errors' in config",
+                "--pattern=error from profile 'rust-errors' in config",
+                "--profile=rust-errors from command line",
+            ],
+            sources
+        );
+
+        assert_eq!(
+            Err("unknown profile 'nope' in config".to_string()),
+            with_file(&["--profile=nope", "to"], false).map(|_| ())
+        );
+        assert_eq!(
+            Err("profile 'bad' in config: unexpected argument 'src': only flags can be set there".to_string()),
+            with_file(&["--profile=bad", "to"], false).map(|_| ())
+        );
+        assert_eq!(
+            Err("no config file to read profile 'x' from".to_string()),
+            parse(&["--profile", "x", "to"]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn switches_can_be_turned_off() {
+        let file = ConfigFile::parse(Path::new("config"), "--line-number\n--hidden\n--no-ignore\n").unwrap();
+        let args: Vec<String> = ["--no-line-number", "--ignore", "--no-ignore-case", "to"].map(String::from).to_vec();
+        let config = Config::parse(&args, Some(&file), true).unwrap();
+        assert!(config.hidden && !config.line_number && !config.no_ignore && !config.ignore_case);
+        let sources: Vec<String> = config.settings_by_option().iter().map(|setting| setting.to_string()).collect();
+        assert_eq!(
+            vec![
+                "--ignore-case from environment variable IGNORE_CASE",
+                "--no-ignore-case from command line",
+                "--line-number from config file config",
+                "--no-line-number from command line",
+                "--hidden from config file config",
+                "--no-ignore from config file config",
+                "--ignore from command line",
+            ],
+            sources
+        );
+        let no_help = parse(&["--no-help"]).map(|_| ());
+        assert_eq!(Err("unknown flag '--no-help' (see 'minigrep --help')".to_string()), no_help);
+    }
+
+    #[test]
+    fn config_file_errors_do_not_stop_help() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let unreadable = || Err("cannot read config file x: denied".to_string());
+        assert!(Config::load(&args(&["--help"]), unreadable(), false).unwrap().show_help);
+        assert!(Config::load(&args(&["-V"]), unreadable(), false).unwrap().show_version);
+        let search = Config::load(&args(&["to"]), unreadable(), false).map(|_| ());
+        assert_eq!(Err("cannot read config file x: denied".to_string()), search);
+
+        let bad = ConfigFile::parse(Path::new("config"), "--nope\n").unwrap();
+        assert!(Config::load(&args(&["--help"]), Ok(Some(bad.clone())), false).unwrap().show_help);
+        assert_eq!(
+            Err("config file config: unknown flag '--nope' (see 'minigrep --help')".to_string()),
+            Config::load(&args(&["to"]), Ok(Some(bad)), false).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn index_build_takes_directories() {
+        let config = parse(&["index", "build", "--hidden", "src", "tests"]).unwrap();
+        assert!(config.build_index && config.hidden);
+        assert_eq!(vec!["src", "tests"], config.paths);
+        assert_eq!(Err("not enough arguments: missing DIR".to_string()), parse(&["index", "build"]).map(|_| ()));
+
+        // anywhere else, `index` is a pattern like any other
+        let config = parse(&["--index", "index", "build"]).unwrap();
+        assert!(config.index && !config.build_index);
+        assert_eq!((vec!["index".to_string()], vec!["build".to_string()]), (config.patterns, config.paths));
+    }
+
+    #[test]
+    fn errors_are_descriptive() {
+        assert_eq!(Err("unknown flag '-y' (see 'minigrep --help')".to_string()), parse(&["-y", "to", "poem.txt"]).map(|_| ()));
+        assert_eq!(Err("unknown flag '--nope' (see 'minigrep --help')".to_string()), parse(&["--nope"]).map(|_| ()));
+        assert_eq!(Err("not enough arguments: missing PATTERN".to_string()), parse(&["-n"]).map(|_| ()));
+        assert_eq!(Err("flag '-A' needs a value".to_string()), parse(&["to", "-A"]).map(|_| ()));
+        assert_eq!(Err("flag '--count' does not take a value".to_string()), parse(&["--count=1"]).map(|_| ()));
+        assert_eq!(
+            Err("invalid value 'x' for '--context': expected a number".to_string()),
+            parse(&["-Cx", "to"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("--json cannot be combined with -c, -l or -L".to_string()),
+            parse(&["--json", "-c", "to"]).map(|_| ())
+        );
+        assert_eq!(Err("--write needs --replace".to_string()), parse(&["--write", "to"]).map(|_| ()));
+        assert_eq!(
+            Err("invalid value 'klingon' for '--encoding': unknown encoding".to_string()),
+            parse(&["--encoding", "klingon", "to"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("--multiline cannot be combined with -v, -A, -B, -C, --json, --write or --follow".to_string()),
+            parse(&["-UC1", "to"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("--fuzzy cannot be combined with --regex, -w, -x or --multiline".to_string()),
+            parse(&["--fuzzy=1", "--regex", "to"]).map(|_| ())
+        );
+    }
+}
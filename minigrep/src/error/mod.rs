@@ -0,0 +1,119 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+use crate::io::display_name;
+
+/// Everything that can go wrong in minigrep.
+///
+/// A file that cannot be searched does not stop a run: it is reported as an
+/// [`Error::Io`] on standard error and counted in the run's [`Outcome`].
+/// Everything else ends the run.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be understood.
+    Args(String),
+    /// A pattern is not a valid regular expression.
+    Pattern(regex::Error),
+    /// A `--glob` or `--type` filter is invalid.
+    Filter(ignore::Error),
+    /// A file or directory could not be read, or rewritten.
+    Io { path: PathBuf, source: io::Error },
+    /// The results could not be written out.
+    Output(io::Error),
+}
+
+impl Error {
+    /// The exit status after an error, as in grep.
+    pub const EXIT_CODE: i32 = 2;
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Error {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Args(message) => f.write_str(message),
+            Error::Pattern(err) => write!(f, "invalid pattern: {}", err),
+            Error::Filter(err) => write!(f, "invalid filter: {}", err),
+            Error::Io { path, source } => write!(f, "{}: {}", display_name(path), source),
+            Error::Output(err) => write!(f, "cannot write output: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Args(_) => None,
+            Error::Pattern(err) => Some(err),
+            Error::Filter(err) => Some(err),
+            Error::Io { source, .. } => Some(source),
+            Error::Output(err) => Some(err),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Error {
+        Error::Pattern(err)
+    }
+}
+
+impl From<ignore::Error> for Error {
+    fn from(err: ignore::Error) -> Error {
+        Error::Filter(err)
+    }
+}
+
+/// How a run that was not stopped by an [`Error`] went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// Whether a line was selected; with `-L`, whether a file was named,
+    /// with `--write`, whether a file changed, and with `index build`,
+    /// always.
+    pub matched: bool,
+    /// How many files could not be searched.
+    pub errors: usize,
+}
+
+impl Outcome {
+    /// grep's exit status: 0 when something matched, 1 when nothing did,
+    /// and [`Error::EXIT_CODE`] when a file could not be searched, whatever
+    /// the others held.
+    pub fn exit_code(&self) -> i32 {
+        if self.errors > 0 {
+            Error::EXIT_CODE
+        } else if self.matched {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn io_errors_name_the_file_and_keep_the_source() {
+        let err = Error::io("-", io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!("(standard input): permission denied", err.to_string());
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io::ErrorKind::PermissionDenied, source.kind());
+    }
+
+    #[test]
+    fn exit_codes_follow_grep() {
+        assert_eq!(0, Outcome { matched: true, errors: 0 }.exit_code());
+        assert_eq!(1, Outcome::default().exit_code());
+        assert_eq!(2, Outcome { matched: true, errors: 1 }.exit_code());
+    }
+}
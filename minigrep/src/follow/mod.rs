@@ -0,0 +1,210 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// How long [`Tail`] waits before looking for more input again.
+const POLL: Duration = Duration::from_millis(200);
+
+/// What happened to a followed file to make [`Tail`] start over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The file shrank, usually to nothing, as `copytruncate` leaves it.
+    Truncated,
+    /// Another file was put in its place, as renaming log rotators do.
+    Replaced,
+}
+
+/// Reads a file that is still being written, like `tail -F`: at the end of
+/// the file a read waits for more to be appended instead of returning.
+///
+/// A read only ends the input, by returning 0, once the file was truncated
+/// or replaced; [`Tail::restart`] then reads the file from the start again,
+/// or the new file at the same path.
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    file: File,
+    position: u64,
+    rotated: Option<Rotation>,
+}
+
+impl Tail {
+    pub fn open(path: &Path) -> io::Result<Tail> {
+        Ok(Tail {
+            path: path.to_path_buf(),
+            file: File::open(path)?,
+            position: 0,
+            rotated: None,
+        })
+    }
+
+    /// Starts over after the input ended, and tells why it did. Returns
+    /// `None` if it had not ended, so there is nothing to start over.
+    pub fn restart(&mut self) -> io::Result<Option<Rotation>> {
+        let rotated = self.rotated.take();
+        match rotated {
+            Some(Rotation::Truncated) => {
+                self.file.seek(SeekFrom::Start(0))?;
+            }
+            Some(Rotation::Replaced) => self.file = File::open(&self.path)?,
+            None => return Ok(None),
+        }
+        self.position = 0;
+        Ok(rotated)
+    }
+
+    /// Whether the file was rotated since it was opened.
+    fn rotation(&self) -> io::Result<Option<Rotation>> {
+        let open = self.file.metadata()?;
+        if open.len() < self.position {
+            return Ok(Some(Rotation::Truncated));
+        }
+        match fs::metadata(&self.path) {
+            Ok(current) if !same_file(&open, &current) => Ok(Some(Rotation::Replaced)),
+            // renamed away, and not recreated yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+            Ok(_) => Ok(None),
+        }
+    }
+}
+
+impl Read for Tail {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rotated.is_some() || buf.is_empty() {
+            return Ok(0);
+        }
+        let mut replaced = false;
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                return Ok(read);
+            }
+            if replaced {
+                self.rotated = Some(Rotation::Replaced);
+                return Ok(0);
+            }
+            match self.rotation()? {
+                Some(Rotation::Truncated) => {
+                    self.rotated = Some(Rotation::Truncated);
+                    return Ok(0);
+                }
+                // whatever was appended to the old file before it was
+                // replaced still has to be read
+                Some(Rotation::Replaced) => replaced = true,
+                None => thread::sleep(POLL),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    // without inodes to compare, only truncation is noticed
+    true
+}
+
+/// Collects output and writes it to `inner` a batch of whole lines at a
+/// time, so that several files followed at once never have their lines
+/// interleaved halfway.
+#[derive(Debug)]
+pub struct WholeLines<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> WholeLines<W> {
+    pub fn new(inner: W) -> WholeLines<W> {
+        WholeLines { inner, buf: Vec::new() }
+    }
+}
+
+impl<W: Write> Write for WholeLines<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if let Some(newline) = memchr::memrchr(b'\n', &self.buf) {
+            self.inner.write_all(&self.buf[..=newline])?;
+            self.inner.flush()?;
+            self.buf.drain(..=newline);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.buf)?;
+        self.buf.clear();
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_some(tail: &mut Tail) -> String {
+        let mut buf = [0; 64];
+        let read = tail.read(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..read]).into_owned()
+    }
+
+    fn append(path: &Path, text: &str) {
+        File::options().append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn follows_appends_truncation_and_replacement() {
+        let path = std::env::temp_dir().join(format!("minigrep-follow-{}.log", std::process::id()));
+        fs::write(&path, "a\n").unwrap();
+        let mut tail = Tail::open(&path).unwrap();
+        assert_eq!("a\n", read_some(&mut tail));
+
+        let writer = thread::spawn({
+            let path = path.clone();
+            move || {
+                thread::sleep(POLL / 2);
+                append(&path, "b\n");
+            }
+        });
+        assert_eq!("b\n", read_some(&mut tail));
+        writer.join().unwrap();
+        assert_eq!(None, tail.restart().unwrap());
+
+        fs::write(&path, "c\n").unwrap();
+        assert_eq!("", read_some(&mut tail));
+        assert_eq!(Some(Rotation::Truncated), tail.restart().unwrap());
+        assert_eq!("c\n", read_some(&mut tail));
+
+        // lines written just before the rotation are not lost
+        append(&path, "d\n");
+        let rotated = path.with_extension("log.1");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "e\n").unwrap();
+        assert_eq!("d\n", read_some(&mut tail));
+        assert_eq!("", read_some(&mut tail));
+        assert_eq!(Some(Rotation::Replaced), tail.restart().unwrap());
+        assert_eq!("e\n", read_some(&mut tail));
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(rotated).unwrap();
+    }
+
+    #[test]
+    fn whole_lines_are_written_at_once() {
+        let mut out = WholeLines::new(Vec::new());
+        write!(out, "file:").unwrap();
+        assert!(out.inner.is_empty());
+        write!(out, "line\nnext:").unwrap();
+        assert_eq!(b"file:line\n", &out.inner[..]);
+        out.flush().unwrap();
+        assert_eq!(b"file:line\nnext:", &out.inner[..]);
+    }
+}
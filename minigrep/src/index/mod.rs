@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use encoding_rs::Encoding;
+
+use crate::error::Error;
+use crate::io::{Compression, MmapChoice};
+use crate::walk::{self, WalkOptions};
+
+mod query;
+
+pub use query::{trigram, trigrams, Query};
+
+/// The name of the index file `minigrep index build DIR` writes into DIR.
+pub const FILE_NAME: &str = ".minigrep-index";
+
+/// Whether `name` is that of an index file, or of one still being written.
+pub fn is_index_file(name: &OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| name.strip_prefix(FILE_NAME).is_some_and(|rest| rest.is_empty() || rest.starts_with('-')))
+}
+
+const MAGIC: &[u8] = b"minigrep index 1\n";
+
+/// Files modified this shortly before an index is built are not indexed:
+/// a change in the same tick of a coarse file system clock would leave
+/// their modification time as it was, and the index wrong.
+const RACY: Duration = Duration::from_secs(2);
+
+/// The trigrams of every file below a directory, so that searches can skip
+/// the files that cannot match without reading them.
+///
+/// Each file is recorded with its size and modification time, and the
+/// index is only trusted for files on which both are unchanged: files that
+/// were added or modified since the index was built are searched as usual.
+#[derive(Debug, Default)]
+pub struct Index {
+    /// Files by their path relative to the indexed directory.
+    files: HashMap<PathBuf, Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    size: u64,
+    /// Nanoseconds since the Unix epoch.
+    modified: u64,
+    /// Sorted; `None` for files the index cannot vouch for, which are
+    /// always searched: compressed files, files in an encoding other than
+    /// UTF-8, and files that changed while the index was being built.
+    trigrams: Option<Vec<u32>>,
+}
+
+impl Entry {
+    fn is_current(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && Some(self.modified) == modified(metadata)
+    }
+}
+
+/// How [`Index::build`] got on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Files read and indexed.
+    pub read: usize,
+    /// Files taken over from the previous index because they are unchanged.
+    pub unchanged: usize,
+}
+
+impl Index {
+    /// Where the index of `dir` is kept.
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(FILE_NAME)
+    }
+
+    /// Reads the index of `dir`, or returns `None` if it has none.
+    pub fn open(dir: &Path) -> io::Result<Option<Index>> {
+        match File::open(Index::path(dir)) {
+            Ok(file) => Index::read(BufReader::new(file)).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Indexes the files below `dir` that a search with `options` would
+    /// walk. Files that have not changed since `previous` was built are not
+    /// read again. Files that cannot be read are reported through
+    /// `on_error` and left out.
+    pub fn build<F>(dir: &Path, options: &WalkOptions, previous: &Index, mut on_error: F) -> Result<(Index, BuildStats), Error>
+    where
+        F: FnMut(&Path, io::Error),
+    {
+        let started = SystemTime::now();
+        let mut index = Index::default();
+        let mut stats = BuildStats::default();
+        for path in walk::files(&[dir.to_string_lossy().into_owned()], options, &mut on_error)? {
+            let Ok(relative) = path.strip_prefix(dir) else { continue };
+            if relative.to_str().is_none() {
+                continue;
+            }
+            let entry = match fs::metadata(&path) {
+                Ok(metadata) => match previous.files.get(relative) {
+                    Some(entry) if entry.trigrams.is_some() && entry.is_current(&metadata) => {
+                        stats.unchanged += 1;
+                        Ok(entry.clone())
+                    }
+                    _ => {
+                        stats.read += 1;
+                        index_file(&path, &metadata, started)
+                    }
+                },
+                Err(e) => Err(e),
+            };
+            match entry {
+                Ok(entry) => {
+                    index.files.insert(relative.to_path_buf(), entry);
+                }
+                Err(e) => on_error(&path, e),
+            }
+        }
+        Ok((index, stats))
+    }
+
+    /// Writes the index into `dir`, replacing the one that was there.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let path = Index::path(dir);
+        let temporary = dir.join(format!("{}-{}", FILE_NAME, process::id()));
+        let written = (|| {
+            let mut out = BufWriter::new(File::create(&temporary)?);
+            self.write(&mut out)?;
+            out.into_inner()?.sync_all()?;
+            fs::rename(&temporary, &path)
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        written
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Whether `query` may match in the file at `relative`, which is false
+    /// only when the index knows the file as it is now and the file lacks
+    /// the trigrams `query` needs.
+    pub fn admits(&self, relative: &Path, metadata: &Metadata, query: &Query) -> bool {
+        match self.files.get(relative) {
+            Some(entry) if entry.is_current(metadata) => entry.trigrams.as_ref().is_none_or(|trigrams| query.admits(trigrams)),
+            _ => true,
+        }
+    }
+
+    /// The on-disk format: [`MAGIC`], then the number of files and, for
+    /// each, its path, size, modification time and trigrams, all numbers
+    /// as LEB128 varints. Trigrams are stored as the differences between
+    /// consecutive ones, after their count plus one, or 0 for `None`.
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        // sorted, so that the same tree always gives the same file
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+        write_varint(out, files.len() as u64)?;
+        for (path, entry) in files {
+            let path = path.to_str().expect("only UTF-8 paths are indexed").as_bytes();
+            write_varint(out, path.len() as u64)?;
+            out.write_all(path)?;
+            write_varint(out, entry.size)?;
+            write_varint(out, entry.modified)?;
+            match &entry.trigrams {
+                None => write_varint(out, 0)?,
+                Some(trigrams) => {
+                    write_varint(out, trigrams.len() as u64 + 1)?;
+                    let mut last = 0;
+                    for &trigram in trigrams {
+                        write_varint(out, u64::from(trigram - last))?;
+                        last = trigram;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(mut input: R) -> io::Result<Index> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not a minigrep index, or one from another version"));
+        }
+        let mut index = Index::default();
+        for _ in 0..read_varint(&mut input)? {
+            let len = read_varint(&mut input)?;
+            if len > u64::from(u16::MAX) {
+                return Err(invalid("path too long"));
+            }
+            let mut path = vec![0; len as usize];
+            input.read_exact(&mut path)?;
+            let path = String::from_utf8(path).map_err(|_| invalid("path is not UTF-8"))?;
+            let size = read_varint(&mut input)?;
+            let modified = read_varint(&mut input)?;
+            let trigrams = match read_varint(&mut input)? {
+                0 => None,
+                count => {
+                    let mut trigrams = Vec::new();
+                    let mut last = 0u32;
+                    for _ in 1..count {
+                        let delta = u32::try_from(read_varint(&mut input)?).map_err(|_| invalid("bad trigram"))?;
+                        last = last.checked_add(delta).ok_or_else(|| invalid("bad trigram"))?;
+                        trigrams.push(last);
+                    }
+                    Some(trigrams)
+                }
+            };
+            index.files.insert(
+                PathBuf::from(path),
+                Entry {
+                    size,
+                    modified,
+                    trigrams,
+                },
+            );
+        }
+        Ok(index)
+    }
+}
+
+/// Reads the file at `path` into an [`Entry`].
+fn index_file(path: &Path, metadata: &Metadata, started: SystemTime) -> io::Result<Entry> {
+    let map = crate::io::map(path, MmapChoice::Auto)?;
+    let read;
+    let contents = match &map {
+        Some(map) => &map[..],
+        None => {
+            read = fs::read(path)?;
+            &read[..]
+        }
+    };
+    let name = path.to_string_lossy();
+    // what is searched is not what is on disk
+    let transformed = Compression::detect(&name, contents).is_some() || Encoding::for_bom(contents).is_some();
+    let racy = metadata.modified().map_or(true, |modified| modified + RACY > started);
+    Ok(Entry {
+        size: metadata.len(),
+        modified: modified(metadata).unwrap_or(0),
+        trigrams: (!transformed && !racy).then(|| trigrams(contents)),
+    })
+}
+
+fn modified(metadata: &Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+fn write_varint<W: Write>(out: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("number too large"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid index: {}", message))
+}
+
+/// The indexes of the directories a search was asked to walk, used to skip
+/// the files that [`Query`] rules out.
+#[derive(Debug)]
+pub struct Candidates {
+    indexes: Vec<(PathBuf, Index)>,
+    query: Query,
+}
+
+impl Candidates {
+    /// Opens the index of every directory in `paths`; directories without
+    /// one are reported through `on_missing` and searched in full.
+    pub fn new<F>(paths: &[String], query: Query, mut on_missing: F) -> Result<Candidates, Error>
+    where
+        F: FnMut(&Path),
+    {
+        let mut indexes = Vec::new();
+        for path in paths.iter().map(Path::new).filter(|path| path.is_dir()) {
+            match Index::open(path).map_err(|e| Error::io(Index::path(path), e))? {
+                Some(index) => indexes.push((path.to_path_buf(), index)),
+                None => on_missing(path),
+            }
+        }
+        Ok(Candidates { indexes, query })
+    }
+
+    /// Whether the file at `path` has to be searched.
+    pub fn may_match(&self, path: &Path) -> bool {
+        let Some((relative, index)) = self
+            .indexes
+            .iter()
+            .find_map(|(dir, index)| Some((path.strip_prefix(dir).ok()?, index)))
+        else {
+            return true;
+        };
+        match fs::metadata(path) {
+            Ok(metadata) => index.admits(relative, &metadata, &self.query),
+            // let the search report it
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MatchOptions;
+
+    fn query(pattern: &str) -> Query {
+        Query::new(&[pattern], MatchOptions::default())
+    }
+
+    #[test]
+    fn builds_incrementally_and_narrows_searches() {
+        let dir = std::env::temp_dir().join(format!("minigrep-index-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let long_ago = SystemTime::now() - Duration::from_secs(60);
+        let write = |name: &str, contents: &str| {
+            fs::write(dir.join(name), contents).unwrap();
+            File::options().write(true).open(dir.join(name)).unwrap().set_modified(long_ago).unwrap();
+        };
+        write("a.txt", "needle in a haystack\n");
+        write("sub/b.txt", "only hay\n");
+        fs::write(dir.join("new.txt"), "no needle yet\n").unwrap();
+
+        let (index, stats) = Index::build(&dir, &WalkOptions::default(), &Index::default(), |_, e| panic!("{}", e)).unwrap();
+        assert_eq!(BuildStats { read: 3, unchanged: 0 }, stats);
+        index.save(&dir).unwrap();
+
+        let candidates = Candidates::new(&[dir.to_string_lossy().into_owned()], query("needle"), |_| panic!()).unwrap();
+        assert!(candidates.may_match(&dir.join("a.txt")));
+        assert!(!candidates.may_match(&dir.join("sub/b.txt")));
+        // modified too recently to be trusted
+        assert!(candidates.may_match(&dir.join("new.txt")));
+
+        write("sub/b.txt", "hay, and a needle\n");
+        assert!(candidates.may_match(&dir.join("sub/b.txt")));
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        let (index, stats) = Index::build(&dir, &WalkOptions::default(), &index, |_, e| panic!("{}", e)).unwrap();
+        assert_eq!(BuildStats { read: 2, unchanged: 0 }, stats);
+        assert_eq!(2, index.len());
+
+        let (_, stats) = Index::build(&dir, &WalkOptions::default(), &index, |_, e| panic!("{}", e)).unwrap();
+        assert_eq!(BuildStats { read: 1, unchanged: 1 }, stats);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_its_file_format() {
+        let mut index = Index::default();
+        let entry = |trigrams| Entry {
+            size: 300,
+            modified: 1_700_000_000_000_000_000,
+            trigrams,
+        };
+        index.files.insert(PathBuf::from("src/ü.rs"), entry(Some(trigrams(b"hello, world"))));
+        index.files.insert(PathBuf::from("big.gz"), entry(None));
+
+        let mut bytes = Vec::new();
+        index.write(&mut bytes).unwrap();
+        assert_eq!(index.files, Index::read(&bytes[..]).unwrap().files);
+        assert!(Index::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Index::read(&b"minigrep index 0\n"[..]).is_err());
+    }
+}
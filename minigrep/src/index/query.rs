@@ -0,0 +1,170 @@
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::search::MatchOptions;
+
+/// The trigrams a file has to contain for a set of patterns to possibly
+/// match in it.
+///
+/// A query never rules out a file that could match, but it may let through
+/// files that do not: whatever it cannot reason about, such as character
+/// classes or literals shorter than three bytes, counts as [`Query::All`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Any file may match.
+    All,
+    /// No file can match.
+    None,
+    Trigram(u32),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Query {
+    /// The query for lines that match any of `patterns`, compiled with
+    /// `options` as [`crate::search::Matcher::any`] does.
+    ///
+    /// Indexes hold the raw bytes of files, so case-insensitive searches,
+    /// which full case folding lets match bytes that are not in the
+    /// pattern, and patterns that look for U+FFFD, which stands in for
+    /// invalid UTF-8, cannot be narrowed down; nor can fuzzy searches,
+    /// whose matches need not contain any trigram of a pattern.
+    pub fn new<S: AsRef<str>>(patterns: &[S], options: MatchOptions) -> Query {
+        if options.ignore_case
+            || options.fuzzy.is_some()
+            || patterns.iter().any(|pattern| pattern.as_ref().contains('\u{fffd}'))
+        {
+            return Query::All;
+        }
+        Query::or(patterns.iter().map(|pattern| {
+            let pattern = pattern.as_ref();
+            if !options.regex {
+                return Query::literal(pattern.as_bytes());
+            }
+            match regex_syntax::parse(pattern) {
+                Ok(hir) => Query::hir(&hir),
+                // the pattern is rejected before any file is searched
+                Err(_) => Query::All,
+            }
+        }))
+    }
+
+    fn literal(bytes: &[u8]) -> Query {
+        Query::and(bytes.windows(3).map(|window| Query::Trigram(trigram(window))))
+    }
+
+    fn hir(hir: &Hir) -> Query {
+        match hir.kind() {
+            HirKind::Literal(literal) => Query::literal(&literal.0),
+            HirKind::Capture(capture) => Query::hir(&capture.sub),
+            HirKind::Repetition(repetition) if repetition.min > 0 => Query::hir(&repetition.sub),
+            HirKind::Concat(subs) => Query::and(subs.iter().map(Query::hir)),
+            HirKind::Alternation(subs) => Query::or(subs.iter().map(Query::hir)),
+            _ => Query::All,
+        }
+    }
+
+    fn and(queries: impl Iterator<Item = Query>) -> Query {
+        let mut all = Vec::new();
+        for query in queries {
+            match query {
+                Query::All => {}
+                Query::None => return Query::None,
+                Query::And(queries) => all.extend(queries),
+                query => all.push(query),
+            }
+        }
+        match all.len() {
+            0 => Query::All,
+            1 => all.pop().unwrap(),
+            _ => Query::And(all),
+        }
+    }
+
+    fn or(queries: impl Iterator<Item = Query>) -> Query {
+        let mut any = Vec::new();
+        for query in queries {
+            match query {
+                Query::All => return Query::All,
+                Query::None => {}
+                Query::Or(queries) => any.extend(queries),
+                query => any.push(query),
+            }
+        }
+        match any.len() {
+            0 => Query::None,
+            1 => any.pop().unwrap(),
+            _ => Query::Or(any),
+        }
+    }
+
+    /// Whether a file with `trigrams`, sorted, may match.
+    pub fn admits(&self, trigrams: &[u32]) -> bool {
+        match self {
+            Query::All => true,
+            Query::None => false,
+            Query::Trigram(trigram) => trigrams.binary_search(trigram).is_ok(),
+            Query::And(queries) => queries.iter().all(|query| query.admits(trigrams)),
+            Query::Or(queries) => queries.iter().any(|query| query.admits(trigrams)),
+        }
+    }
+}
+
+/// Three bytes packed into the low 24 bits of a `u32`.
+pub fn trigram(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2])
+}
+
+/// Every distinct trigram in `bytes`, sorted.
+pub fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = bytes.windows(3).map(trigram).collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admits(patterns: &[&str], regex: bool, text: &str) -> bool {
+        let options = MatchOptions {
+            regex,
+            ..MatchOptions::default()
+        };
+        Query::new(patterns, options).admits(&trigrams(text.as_bytes()))
+    }
+
+    #[test]
+    fn literals_need_all_their_trigrams() {
+        assert!(admits(&["needle"], false, "a needle here"));
+        assert!(!admits(&["needle"], false, "a needl here"));
+        assert!(admits(&["nope", "needle"], false, "a needle here"));
+        // too short to say anything
+        assert!(admits(&["ab"], false, "xyz"));
+        assert!(!admits(&[], false, "xyz"));
+    }
+
+    #[test]
+    fn regexes_need_their_required_literals() {
+        assert!(admits(&[r"foo\d+bar"], true, "foo bar"));
+        assert!(!admits(&[r"foo\d+bar"], true, "foo baz"));
+        assert!(admits(&["(alpha|beta)gamma"], true, "betagamma"));
+        assert!(!admits(&["(alpha|beta)gamma"], true, "alpha beta"));
+        assert!(admits(&["(?:needle)?x"], true, "x"));
+        assert!(admits(&["(?i)needle"], true, "NEEDLE"));
+    }
+
+    #[test]
+    fn case_folding_is_not_narrowed() {
+        let options = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(Query::All, Query::new(&["ﬁle"], options));
+        let options = MatchOptions {
+            fuzzy: Some(1),
+            ..MatchOptions::default()
+        };
+        assert_eq!(Query::All, Query::new(&["needle"], options));
+    }
+}
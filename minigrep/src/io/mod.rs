@@ -1,18 +1,382 @@
-use std::fs::read_to_string;
-
-pub fn read_lines(filename: &String) -> Vec<String> {
-    let lines = match read_to_string(filename) {
-        Ok(v) => v,
-        Err(e) => {
-            println!("error opening file {}, error = {}", filename, e.to_string());
-            return Vec::new();
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use memchr::memchr;
+use memmap2::Mmap;
+
+/// The path that stands for standard input, as in most Unix tools.
+pub const STDIN: &str = "-";
+
+pub fn read_lines(filename: &str) -> io::Result<Lines<BufReader<File>>> {
+    let file = File::open(filename)?;
+    Ok(BufReader::new(file).lines())
+}
+
+/// Opens a file, or standard input for [`STDIN`], as a buffered reader.
+pub fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    if path == Path::new(STDIN) {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    Ok(Box::new(BufReader::new(File::open(path)?)))
+}
+
+/// Files at least this large are mapped into memory by [`MmapChoice::Auto`];
+/// below it, setting up and tearing down a mapping costs as much as copying
+/// saves, as `benches/mmap.rs` shows.
+pub const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// When files are mapped into memory rather than read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MmapChoice {
+    /// Regular files of at least [`MMAP_THRESHOLD`] bytes.
+    #[default]
+    Auto,
+    /// Every regular file that is not empty.
+    Always,
+    Never,
+}
+
+/// Maps the file at `path` into memory if `choice` says so. Standard input,
+/// pipes and other files that are not regular files are never mapped.
+pub fn map(path: &Path, choice: MmapChoice) -> io::Result<Option<Mmap>> {
+    if choice == MmapChoice::Never || path == Path::new(STDIN) {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(path)?;
+    let threshold = match choice {
+        MmapChoice::Auto => MMAP_THRESHOLD,
+        _ => 1,
+    };
+    if !metadata.is_file() || metadata.len() < threshold {
+        return Ok(None);
+    }
+    let file = File::open(path)?;
+    // SAFETY: the mapping is only read. Like every grep that maps files,
+    // minigrep may see odd contents, or be killed by SIGBUS, if another
+    // process truncates the file while it is being searched; --no-mmap
+    // avoids that.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(Some(map))
+}
+
+/// The contents of a file, as they are searched.
+pub enum Input<'a> {
+    /// Bytes that are already in memory, usually a mapped file.
+    Mapped(&'a [u8]),
+    Stream(Box<dyn BufRead + 'a>),
+}
+
+impl Input<'_> {
+    /// [`is_binary`] for either kind of input, looking at as much of a
+    /// mapping as would be in the first buffer of a stream.
+    pub fn is_binary(&mut self) -> io::Result<bool> {
+        match self {
+            Input::Mapped(bytes) => is_binary(&mut &bytes[..bytes.len().min(BINARY_PREFIX)]),
+            Input::Stream(reader) => is_binary(reader),
         }
+    }
+}
+
+/// How much of a mapping [`Input::is_binary`] looks at: the capacity of a
+/// default `BufReader`.
+const BINARY_PREFIX: usize = 8 * 1024;
+
+/// Reads a file, or standard input for [`STDIN`], as one pattern per line.
+pub fn read_patterns(path: &Path) -> io::Result<Vec<String>> {
+    open(path)?.lines().collect()
+}
+
+/// Replaces the contents of the file at `path` atomically, keeping the
+/// previous contents at [`backup_path`].
+///
+/// The new contents go to a temporary file next to `path`, with the same
+/// permissions, which is then renamed over it: a reader sees either the old
+/// file or the new one, and a failure leaves the old one in place.
+pub fn write_with_backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = path.file_name().ok_or_else(|| io::Error::other("not a file"))?;
+    let mut temporary = name.to_os_string();
+    temporary.push(format!(".minigrep-{}", process::id()));
+    let temporary = path.with_file_name(temporary);
+
+    let written = (|| {
+        let mut file = File::create(&temporary)?;
+        file.write_all(contents)?;
+        file.set_permissions(fs::metadata(path)?.permissions())?;
+        file.sync_all()?;
+
+        let backup = backup_path(path);
+        match fs::remove_file(&backup) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        // a hard link keeps the original file as it is, without copying it
+        if fs::hard_link(path, &backup).is_err() {
+            fs::copy(path, &backup)?;
+        }
+        fs::rename(&temporary, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Where [`write_with_backup`] keeps the previous contents of `path`: the
+/// same name with `.bak` appended.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// The name a path is reported under in output and warnings.
+pub fn display_name(path: &Path) -> Cow<'_, str> {
+    if path == Path::new(STDIN) {
+        Cow::Borrowed("(standard input)")
+    } else {
+        path.to_string_lossy()
+    }
+}
+
+/// Decodes `bytes` as UTF-8, replacing every invalid sequence with
+/// U+FFFD REPLACEMENT CHARACTER, so that text with a few stray bytes in it
+/// can still be searched. Valid input is borrowed, not copied.
+pub fn text(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+/// Puts a decoder in front of `reader` when its input is not plain UTF-8:
+/// when it starts with a byte order mark, or `encoding` is given.
+///
+/// The byte order mark wins over `encoding`, and is stripped. Without
+/// either, the input is passed through untouched and is taken to be UTF-8.
+/// Characters that cannot be decoded become U+FFFD.
+pub fn decode<'r>(mut reader: Box<dyn BufRead + 'r>, encoding: Option<&'static Encoding>) -> io::Result<Box<dyn BufRead + 'r>> {
+    if !needs_decoding(reader.fill_buf()?, encoding) {
+        return Ok(reader);
+    }
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .build(reader);
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+/// A compression format `-z` looks through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Recognises the format of an input from its first bytes, or failing
+    /// that from the extension of `path`.
+    pub fn detect(path: &str, start: &[u8]) -> Option<Compression> {
+        match start {
+            [0x1f, 0x8b, ..] => return Some(Compression::Gzip),
+            // `BZh` and the block size, so that text starting with `BZh`
+            // is not taken for bzip2
+            [b'B', b'Z', b'h', b'1'..=b'9', ..] => return Some(Compression::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0, ..] => return Some(Compression::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => return Some(Compression::Zstd),
+            _ => {}
+        }
+        match Path::new(path).extension()?.to_str()? {
+            "gz" | "tgz" => Some(Compression::Gzip),
+            "bz2" | "tbz2" => Some(Compression::Bzip2),
+            "xz" | "txz" => Some(Compression::Xz),
+            "zst" | "tzst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Whether input starting with `start` goes through a decoder in
+/// [`decode`].
+pub fn needs_decoding(start: &[u8], encoding: Option<&'static Encoding>) -> bool {
+    encoding.is_some() || Encoding::for_bom(start).is_some()
+}
+
+/// Puts a decompressor in front of `reader` when the input at `path` is
+/// compressed, as told by [`Compression::detect`], and otherwise passes it
+/// through untouched. Concatenated streams, as left behind by `cat a.gz
+/// b.gz` or some log rotators, are decompressed one after the other.
+pub fn decompress<'r>(mut reader: Box<dyn BufRead + 'r>, path: &str) -> io::Result<Box<dyn BufRead + 'r>> {
+    let decompressed: Box<dyn Read + 'r> = match Compression::detect(path, reader.fill_buf()?) {
+        None => return Ok(reader),
+        Some(Compression::Gzip) => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        Some(Compression::Bzip2) => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        Some(Compression::Xz) => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
     };
+    Ok(Box::new(BufReader::new(decompressed)))
+}
+
+/// Whether `reader` looks like binary data rather than text: a NUL byte in
+/// the first buffer of input, the heuristic grep uses as well. Nothing is
+/// consumed.
+pub fn is_binary<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    Ok(memchr(0, reader.fill_buf()?).is_some())
+}
+
+/// Calls `f` with every line of `reader`, its 1-based line number and the
+/// byte offset at which it starts, until the input ends or `f` breaks.
+///
+/// A single buffer is reused for every line, so memory use depends on the
+/// longest line rather than on the size of the input. Line terminators
+/// (`\n` or `\r\n`) are stripped, as `str::lines` does. Lines that are not
+/// valid UTF-8 are decoded with [`text`]; offsets still count the bytes of
+/// the input.
+pub fn for_each_line<R, F>(mut reader: R, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(usize, usize, &str) -> ControlFlow<()>,
+{
+    let mut buf = Vec::new();
+    let mut number = 0;
+    let mut offset = 0;
+
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        number += 1;
+
+        let line = text(&buf);
+        let line = match line.strip_suffix('\n') {
+            Some(line) => line.strip_suffix('\r').unwrap_or(line),
+            None => &line,
+        };
+
+        if f(number, offset, line).is_break() {
+            return Ok(());
+        }
+        offset += read;
+    }
+}
 
-    let mut result = Vec::new();
-    for line in lines.lines() {
-        result.push(line.to_string())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn collect_decoded(input: &[u8], encoding: Option<&'static Encoding>) -> Vec<String> {
+        let reader = decode(Box::new(Cursor::new(input)), encoding).unwrap();
+        let mut lines = Vec::new();
+        for_each_line(reader, |_, _, line| {
+            lines.push(line.to_string());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        lines
+    }
+
+    fn collect(input: &[u8]) -> io::Result<Vec<(usize, usize, String)>> {
+        let mut lines = Vec::new();
+        for_each_line(Cursor::new(input), |number, offset, line| {
+            lines.push((number, offset, line.to_string()));
+            ControlFlow::Continue(())
+        })?;
+        Ok(lines)
+    }
+
+    #[test]
+    fn strips_terminators_like_str_lines() {
+        let lines = collect(b"one\r\ntwo\n\nthree").unwrap();
+        assert_eq!(
+            vec![(1, 0, "one".into()), (2, 5, "two".into()), (3, 9, "".into()), (4, 10, "three".into())],
+            lines
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let lines = collect(b"caf\xe9\nbin\0ary\n\xff").unwrap();
+        assert_eq!(
+            vec![(1, 0, "caf\u{fffd}".into()), (2, 5, "bin\0ary".into()), (3, 13, "\u{fffd}".into())],
+            lines
+        );
+    }
+
+    #[test]
+    fn decodes_utf16_with_a_byte_order_mark() {
+        let utf16: Vec<u8> = [0xfeff_u16].iter().chain(&"héllo\nwörld".encode_utf16().collect::<Vec<_>>()).flat_map(|unit| unit.to_le_bytes()).collect();
+        let lines = collect_decoded(&utf16, None);
+        assert_eq!(vec!["héllo", "wörld"], lines);
+
+        let latin1 = collect_decoded(b"caf\xe9", Encoding::for_label(b"latin1"));
+        assert_eq!(vec!["café"], latin1);
+        // plain UTF-8 is left alone
+        assert_eq!(vec!["café"], collect_decoded("café".as_bytes(), None));
     }
 
-    result
+    #[test]
+    fn binary_input_is_detected_without_consuming_it() {
+        let mut reader = Cursor::new(b"bin\0ary\n");
+        assert!(is_binary(&mut reader).unwrap());
+        assert_eq!(1, collect(reader.fill_buf().unwrap()).unwrap().len());
+        assert!(!is_binary(&mut Cursor::new(b"text\n")).unwrap());
+    }
+
+    #[test]
+    fn only_chosen_regular_files_are_mapped() {
+        let path = std::env::temp_dir().join(format!("minigrep-map-{}.txt", std::process::id()));
+        fs::write(&path, "small\n").unwrap();
+        assert_eq!(b"small\n", &map(&path, MmapChoice::Always).unwrap().unwrap()[..]);
+        // too small to be worth it
+        assert!(map(&path, MmapChoice::Auto).unwrap().is_none());
+        assert!(map(&path, MmapChoice::Never).unwrap().is_none());
+        assert!(map(Path::new(STDIN), MmapChoice::Always).unwrap().is_none());
+        assert!(map(&std::env::temp_dir(), MmapChoice::Always).unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+
+    fn decompressed(path: &str, input: Vec<u8>) -> String {
+        let mut text = String::new();
+        decompress(Box::new(Cursor::new(input)), path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn compressed_input_is_decompressed() {
+        let text = b"one\ntwo\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(text).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(text).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(text).unwrap();
+        let zstd = zstd::encode_all(&text[..], 0).unwrap();
+
+        // the format is told by the bytes, whatever the file is called
+        assert_eq!("one\ntwo\n", decompressed("log.1", gzip.clone()));
+        assert_eq!("one\ntwo\n", decompressed("log.gz", bzip2.finish().unwrap()));
+        assert_eq!("one\ntwo\n", decompressed("log.xz", xz.finish().unwrap()));
+        assert_eq!("one\ntwo\n", decompressed("log.zst", zstd));
+        // concatenated members are all read
+        assert_eq!("one\ntwo\none\ntwo\n", decompressed("log.gz", [gzip.clone(), gzip].concat()));
+        assert_eq!("plain\n", decompressed("log.txt", b"plain\n".to_vec()));
+    }
+
+    #[test]
+    fn compression_falls_back_to_the_extension() {
+        assert_eq!(Some(Compression::Zstd), Compression::detect("app.log.zst", b""));
+        assert_eq!(Some(Compression::Gzip), Compression::detect("-", b"\x1f\x8b\x08"));
+        assert_eq!(None, Compression::detect("app.log", b"text"));
+        assert_eq!(Some(Compression::Bzip2), Compression::detect("-", b"BZh91AY&SY"));
+        assert_eq!(None, Compression::detect("notes.txt", b"BZh is a prefix"));
+    }
 }
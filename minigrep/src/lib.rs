@@ -1,61 +1,421 @@
+//! minigrep, a small grep, as a command line tool and as a library.
+//!
+//! [`run`] is the whole command line program. To search from another
+//! program, build a [`Searcher`] from a [`search::Matcher`] and hand it a
+//! [`Sink`] that receives the selected lines.
+
+pub mod config;
+pub mod error;
+pub mod follow;
+pub mod index;
 pub mod io;
+pub mod output;
+pub mod parallel;
+pub mod replace;
 pub mod search;
+pub mod walk;
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+pub use config::Config;
+pub use error::{Error, Outcome};
+pub use search::{MatchOptions, SearchOptions, Searcher, Sink};
+use index::{Candidates, Index, Query};
+use follow::{Rotation, Tail, WholeLines};
+use io::Input;
+use output::{Colors, JsonPrinter, Printer, Summary};
+use replace::Replacer;
+use search::{Event, Line, Match, Matcher};
+
+/// Searches as `config` says and prints the results to standard output.
+///
+/// Files that cannot be searched are reported on standard error as they
+/// come up, and the run goes on without them.
+pub fn run(config: Config) -> Result<Outcome, Error> {
+    if config.build_index {
+        return build_indexes(&config);
+    }
+    let mut patterns = config.patterns.clone();
+    for file in &config.pattern_files {
+        let read = io::read_patterns(Path::new(file)).map_err(|e| Error::io(file, e))?;
+        patterns.extend(read);
+    }
+    let searcher = Searcher::new(Matcher::any(&patterns, config.match_options())?, config.search_options());
+    // an inverted search selects lines in files without the trigrams, and
+    // another encoding changes the bytes that are searched
+    let candidates = match Query::new(&patterns, config.match_options()) {
+        query if config.index && !config.invert_match && config.encoding.is_none() && query != Query::All => {
+            let on_missing = |dir: &Path| {
+                eprintln!("minigrep: {}: no index, searching every file", io::display_name(dir));
+            };
+            Some(Candidates::new(&config.paths, query, on_missing)?)
+        }
+        _ => None,
+    };
+    let mut errors = 0;
+    let files = walk::files(&config.paths, &config.walk_options(), |path, e| {
+        errors += 1;
+        warn(&Error::io(path, e));
+    })?;
+    let colors = config.color.enabled().then(Colors::from_env);
+    let threads = match config.threads {
+        Some(0) | None => parallel::default_threads(),
+        Some(threads) => threads,
+    };
+
+    // like `grep -r`, name the file on each line once more than one can
+    // match, or was asked for, even if the others cannot be read
+    let with_filename =
+        files.len() > 1 || config.paths.len() > 1 || config.paths.iter().any(|path| Path::new(path).is_dir());
 
-use std::env;
-use std::error::Error;
-use std::fmt::{Display, Formatter};
-use std::fs::read_to_string;
+    if config.follow {
+        let follow = |path: &PathBuf| follow_file(&config, &searcher, colors.as_ref(), path, with_filename);
+        return Ok(follow_files(&config, &files, follow, errors));
+    }
+
+    let search = |path: &PathBuf, out: &mut dyn Write| {
+        if candidates.as_ref().is_some_and(|candidates| !candidates.may_match(path)) {
+            // the file cannot match, which reads the same as an empty one
+            if config.write {
+                return Ok(0);
+            }
+            return search_file(&config, &searcher, colors.as_ref(), path, with_filename, Input::Mapped(&[]), out);
+        }
+        if config.write {
+            return rewrite_file(&config, searcher.matcher(), colors.as_ref(), path, out);
+        }
+        let map = io::map(path, config.mmap)?;
+        let input = open_input(&config, path, map.as_deref())?;
+        search_file(&config, &searcher, colors.as_ref(), path, with_filename, input, out)
+    };
 
-pub struct Config {
-    pub search_string: String,
-    pub filename: String,
-    pub ignore_case: bool,
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let mut summary = Summary::default();
+
+    if threads == 1 || files.len() == 1 {
+        // print straight to stdout so that a single huge file is never
+        // buffered in memory
+        for path in &files {
+            match search(path, &mut out) {
+                Ok(count) => summary.add(count),
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(outcome(&config, &summary, errors)),
+                Err(e) => {
+                    errors += 1;
+                    warn(&Error::io(path, e));
+                }
+            }
+        }
+    } else {
+        // every file is searched into its own buffer, and the buffers are
+        // printed in the order the files were given
+        let mut write_error = None;
+        parallel::for_each_ordered(
+            &files,
+            threads,
+            |path| {
+                let mut buf = Vec::new();
+                let result = search(path, &mut buf);
+                (buf, result)
+            },
+            |path, (buf, result)| {
+                if let Err(e) = out.write_all(&buf) {
+                    write_error = Some(e);
+                    return false;
+                }
+                match result {
+                    Ok(count) => summary.add(count),
+                    Err(e) => {
+                        errors += 1;
+                        warn(&Error::io(path, e));
+                    }
+                }
+                true
+            },
+        );
+        match write_error {
+            Some(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(outcome(&config, &summary, errors)),
+            Some(e) => return Err(Error::Output(e)),
+            None => {}
+        }
+    }
+
+    if config.json {
+        summary.print(&mut out).map_err(Error::Output)?;
+    }
+    Ok(outcome(&config, &summary, errors))
+}
+
+/// `index build`: indexes every directory in `config.paths`, reusing what
+/// is still current of its previous index.
+fn build_indexes(config: &Config) -> Result<Outcome, Error> {
+    let mut errors = 0;
+    for dir in config.paths.iter().map(Path::new) {
+        // an index that cannot be read is simply rebuilt from scratch
+        let previous = Index::open(dir).ok().flatten().unwrap_or_default();
+        let (index, stats) = Index::build(dir, &config.walk_options(), &previous, |path, e| {
+            errors += 1;
+            warn(&Error::io(path, e));
+        })?;
+        index.save(dir).map_err(|e| Error::io(Index::path(dir), e))?;
+        println!(
+            "{}: indexed {} files ({} read, {} unchanged)",
+            io::display_name(&Index::path(dir)),
+            index.len(),
+            stats.read,
+            stats.unchanged
+        );
+    }
+    Ok(Outcome { matched: true, errors })
 }
 
-impl Config {
-    pub fn new(args: &Vec<String>) -> Result<Config, &str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+/// `--follow`: searches every file in `files` on a thread of its own, and
+/// keeps searching what is appended to them until each search stops.
+fn follow_files<F>(config: &Config, files: &[PathBuf], follow: F, mut errors: usize) -> Outcome
+where
+    F: Fn(&PathBuf) -> std::io::Result<usize> + Sync,
+{
+    let mut summary = Summary::default();
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let follow = &follow;
+        let followers: Vec<_> = files.iter().map(|path| scope.spawn(move || follow(path))).collect();
+        followers.into_iter().map(|follower| follower.join().expect("a follower panicked")).collect()
+    });
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(count) => summary.add(count),
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+            Err(e) => {
+                errors += 1;
+                warn(&Error::io(path, e));
+            }
         }
+    }
+    outcome(config, &summary, errors)
+}
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+/// Searches the file at `path` as it grows, starting over from the top
+/// whenever it is truncated or replaced. Returns once the search stops by
+/// itself, as `-m` makes it.
+fn follow_file(config: &Config, searcher: &Searcher, colors: Option<&Colors>, path: &Path, with_filename: bool) -> std::io::Result<usize> {
+    let mut out = WholeLines::new(std::io::stdout());
+    if path == Path::new(io::STDIN) {
+        let input = transform(config, path, io::open(path)?)?;
+        return search_file(config, searcher, colors, path, with_filename, input, &mut out);
+    }
 
-        Ok(Config {
-            search_string: args[1].clone(),
-            filename: args[2].clone(),
-            ignore_case,
-        })
+    let mut tail = Tail::open(path)?;
+    let mut count = 0;
+    loop {
+        let input = transform(config, path, Box::new(BufReader::new(&mut tail)))?;
+        count += search_file(config, searcher, colors, path, with_filename, input, &mut out)?;
+        out.flush()?;
+        match tail.restart()? {
+            Some(Rotation::Truncated) => eprintln!("minigrep: {}: file truncated", io::display_name(path)),
+            Some(Rotation::Replaced) => eprintln!("minigrep: {}: file replaced, following the new one", io::display_name(path)),
+            None => return Ok(count),
+        }
     }
 }
 
-impl Display for Config {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let _ = write!(f, "================================================================\n");
-        let _ = write!(f, "search_string: {}\nfilename: {}\n", self.search_string, self.filename);
-        let _ = write!(f, "ignore_case: {}\n", self.ignore_case);
-        write!(f, "================================================================\n")
+fn outcome(config: &Config, summary: &Summary, errors: usize) -> Outcome {
+    let matched = if config.files_without_match {
+        summary.searches_with_match < summary.searches
+    } else {
+        summary.matched_lines > 0
+    };
+    Outcome { matched, errors }
+}
+
+/// Gets the file at `path` ready to search. `map`, the file mapped into
+/// memory, is searched where it is unless it has to be decompressed or
+/// decoded first; without it the file is read.
+fn open_input<'a>(config: &Config, path: &Path, map: Option<&'a [u8]>) -> std::io::Result<Input<'a>> {
+    let name = path.to_string_lossy();
+    let reader: Box<dyn BufRead + 'a> = match map {
+        Some(bytes) => {
+            let compressed = config.search_zip && io::Compression::detect(&name, bytes).is_some();
+            if !compressed && !io::needs_decoding(bytes, config.encoding) {
+                return Ok(Input::Mapped(bytes));
+            }
+            Box::new(bytes)
+        }
+        None => io::open(path)?,
+    };
+    transform(config, path, reader)
+}
+
+/// Decompresses and decodes `reader`, the contents of the file at `path`,
+/// as `config` asks.
+fn transform<'a>(config: &Config, path: &Path, mut reader: Box<dyn BufRead + 'a>) -> std::io::Result<Input<'a>> {
+    if config.search_zip {
+        reader = io::decompress(reader, &path.to_string_lossy())?;
     }
+    Ok(Input::Stream(io::decode(reader, config.encoding)?))
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = read_to_string(config.filename)?;
+fn search_file(
+    config: &Config,
+    searcher: &Searcher,
+    colors: Option<&Colors>,
+    path: &Path,
+    with_filename: bool,
+    mut input: Input,
+    out: &mut dyn Write,
+) -> std::io::Result<usize> {
+    let name = io::display_name(path);
+    let list_files = config.files_with_matches || config.files_without_match;
+    // like grep, only tell whether a binary file matches rather than print
+    // its lines, unless -a asks for them; JSON has no notice to give, and
+    // escapes whatever the lines hold, so it reports them as usual
+    let binary = !config.text && !list_files && !config.count && !config.json && input.is_binary()?;
 
-    let lines:Vec<&str>;
-    if config.ignore_case {
-        lines = search::search_case_insensitive(&config.search_string, &contents);
+    let mut report = Report {
+        config,
+        printer: Printer::new(config, searcher.matcher(), colors, with_filename.then_some(name.as_ref())),
+        json: JsonPrinter::new(searcher.matcher(), &name),
+        out,
+        // one match is enough to decide whether to name the file
+        first_only: list_files || binary,
+        count: 0,
+    };
+    if config.multiline {
+        report.regions(searcher.matcher(), input)?;
     } else {
-        lines = search::search(&config.search_string, &contents);
+        searcher.search_input(input, &mut report)?;
+    }
+    let Report { printer, out, count, .. } = report;
+
+    if list_files {
+        if (count > 0) == config.files_with_matches {
+            printer.path(out, &name)?;
+        }
+    } else if config.count {
+        printer.count(out, count)?;
+    } else if binary && count > 0 {
+        printer.binary(out, &name)?;
+    }
+    Ok(count)
+}
+
+/// Prints the lines a search of one file finds, as `config` asks, and
+/// counts the selected ones.
+struct Report<'a> {
+    config: &'a Config,
+    printer: Printer<'a>,
+    json: JsonPrinter<'a>,
+    out: &'a mut dyn Write,
+    /// Stop at the first selected line, and print nothing.
+    first_only: bool,
+    count: usize,
+}
+
+impl Report<'_> {
+    /// `--multiline`: reads all of `input`, and prints the regions
+    /// `matcher` selects in it. For input that is not valid UTF-8, offsets
+    /// count the bytes of the input as decoded.
+    fn regions(&mut self, matcher: &Matcher, input: Input) -> std::io::Result<()> {
+        let read;
+        let bytes = match input {
+            Input::Mapped(bytes) => bytes,
+            Input::Stream(mut reader) => {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)?;
+                read = buf;
+                &read
+            }
+        };
+        let text = io::text(bytes);
+        let max_count = self.config.max_count.unwrap_or(usize::MAX);
+        for region in search::regions(matcher, &text).take(max_count) {
+            self.count += 1;
+            if self.first_only {
+                break;
+            }
+            if !self.config.count {
+                self.printer.region(self.out, &region)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, event: Event) -> std::io::Result<ControlFlow<()>> {
+        if self.first_only || self.config.count {
+            // only the count or the file name is printed, afterwards
+        } else if self.config.json {
+            self.json.event(self.out, &event)?;
+        } else {
+            self.printer.event(self.out, &event)?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+impl Sink for Report<'_> {
+    fn matched(&mut self, m: &Match) -> std::io::Result<ControlFlow<()>> {
+        self.count += 1;
+        if self.first_only {
+            return Ok(ControlFlow::Break(()));
+        }
+        self.print(Event::Match(m.clone()))
     }
 
-    for line in lines {
-        println!("{}", line);
+    fn context(&mut self, line: &Line) -> std::io::Result<ControlFlow<()>> {
+        self.print(Event::Context(line.clone()))
+    }
+
+    fn context_break(&mut self) -> std::io::Result<ControlFlow<()>> {
+        self.print(Event::Break)
     }
-    Ok(())
+}
+
+/// `--write`: replaces the matches in the file at `path` in place, and
+/// names the file if that changed it.
+fn rewrite_file(
+    config: &Config,
+    matcher: &Matcher,
+    colors: Option<&Colors>,
+    path: &Path,
+    out: &mut dyn Write,
+) -> std::io::Result<usize> {
+    if path == Path::new(io::STDIN) {
+        return Err(std::io::Error::other("cannot rewrite standard input"));
+    }
+    let template = config.replace.as_deref().unwrap_or_default();
+    let changed = Replacer::new(matcher, template).rewrite(path, &config.search_options())?;
+    if changed > 0 {
+        Printer::new(config, matcher, colors, None).path(out, &io::display_name(path))?;
+    }
+    Ok(changed)
+}
+
+fn warn(err: &Error) {
+    eprintln!("minigrep: {}", err);
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::search;
+    use std::io::Cursor;
+
+    #[test]
+    fn json_reports_every_match_in_binary_files() {
+        let config = Config {
+            json: true,
+            ..Config::default()
+        };
+        let searcher = Searcher::new(Matcher::new("match", config.match_options()).unwrap(), config.search_options());
+        let input = Input::Stream(Box::new(Cursor::new(&b"match 1\n\0\nmatch 2\n"[..])));
+        let mut out = Vec::new();
+        let count = search_file(&config, &searcher, None, Path::new("bin.dat"), false, input, &mut out).unwrap();
+        assert_eq!(2, count);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(2, out.lines().filter(|line| line.contains(r#""type":"match""#)).count());
+        assert!(!out.contains("Binary file"));
+    }
 
     #[test]
     fn case_sensitive() {
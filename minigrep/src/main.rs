@@ -1,19 +1,40 @@
 use std::env;
-use minigrep::run;
+use std::process;
+
+use minigrep::config::{USAGE, VERSION};
+use minigrep::{run, Error};
 
 fn main() {
     let args_os = env::args().collect::<Vec<_>>();
 
     let conf = minigrep::Config::new(&args_os).unwrap_or_else(|err| {
-        println!("Problem parsing arguments: {}", err);
-        std::process::exit(1);
+        eprintln!("minigrep: {}", err);
+        process::exit(Error::EXIT_CODE);
     });
 
-    println!("{conf}");
+    if conf.debug_config {
+        match &conf.config_file {
+            Some(path) => eprintln!("minigrep: read config file {}", path.display()),
+            None => eprintln!("minigrep: no config file"),
+        }
+        for setting in conf.settings_by_option() {
+            eprintln!("minigrep: {}", setting);
+        }
+    }
+    if conf.show_help {
+        println!("{USAGE}");
+        return;
+    }
+    if conf.show_version {
+        println!("minigrep {VERSION}");
+        return;
+    }
 
-    run(conf).unwrap_or_else(|err| {
-        eprintln!("Application error: {}", err);
-        std::process::exit(1);
-    })
+    match run(conf) {
+        Ok(outcome) => process::exit(outcome.exit_code()),
+        Err(err) => {
+            eprintln!("minigrep: {}", err);
+            process::exit(Error::EXIT_CODE);
+        }
+    }
 }
-
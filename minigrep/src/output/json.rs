@@ -0,0 +1,133 @@
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::search::{Event, Line, Matcher};
+
+/// Prints one JSON object per line of output (JSON Lines), so other
+/// programs can consume results without parsing grep's text format.
+///
+/// Every record has a `type` and a `data` field: `match` and `context`
+/// records describe one line each, and a single `summary` record closes the
+/// stream. Every submatch names the pattern that produced it.
+pub struct JsonPrinter<'a> {
+    matcher: &'a Matcher,
+    path: &'a str,
+}
+
+impl<'a> JsonPrinter<'a> {
+    pub fn new(matcher: &'a Matcher, path: &'a str) -> JsonPrinter<'a> {
+        JsonPrinter { matcher, path }
+    }
+
+    pub fn event(&self, out: &mut dyn Write, event: &Event) -> io::Result<()> {
+        match self.record(event) {
+            Some(record) => writeln!(out, "{}", record),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&self, event: &Event) -> Option<Value> {
+        let record = match event {
+            Event::Match(m) => {
+                let submatches: Vec<Value> = if m.span.is_some() {
+                    self.matcher
+                        .find_iter(m.line.text)
+                        .map(|span| {
+                            json!({
+                                "match": &m.line.text[span.clone()],
+                                "start": span.start,
+                                "end": span.end,
+                                "pattern": self.matcher.query_of(m.line.text, &span),
+                            })
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                self.line("match", &m.line, submatches)
+            }
+            Event::Context(line) => self.line("context", line, Vec::new()),
+            // consumers can tell groups apart from the line numbers
+            Event::Break => return None,
+        };
+        Some(record)
+    }
+
+    fn line(&self, kind: &str, line: &Line, submatches: Vec<Value>) -> Value {
+        json!({
+            "type": kind,
+            "data": {
+                "path": self.path,
+                "line_number": line.number,
+                "absolute_offset": line.offset,
+                "end_offset": line.offset + line.text.len(),
+                "line": line.text,
+                "submatches": submatches,
+            }
+        })
+    }
+}
+
+/// Totals over a whole run, printed as the closing `summary` record.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub matched_lines: usize,
+}
+
+impl Summary {
+    pub fn add(&mut self, count: usize) {
+        self.searches += 1;
+        self.searches_with_match += usize::from(count > 0);
+        self.matched_lines += count;
+    }
+
+    pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        let record = json!({
+            "type": "summary",
+            "data": {
+                "searches": self.searches,
+                "searches_with_match": self.searches_with_match,
+                "matched_lines": self.matched_lines,
+            }
+        });
+        writeln!(out, "{}", record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{Match, MatchOptions};
+
+    #[test]
+    fn match_record_lists_every_submatch() {
+        let matcher = Matcher::new("o", MatchOptions::default()).unwrap();
+        let printer = JsonPrinter::new(&matcher, "poem.txt");
+        let event = Event::Match(Match {
+            line: Line {
+                number: 2,
+                offset: 25,
+                text: "Are you nobody?",
+            },
+            span: Some(5..6),
+        });
+
+        let record = printer.record(&event).unwrap();
+        assert_eq!("match", record["type"]);
+        assert_eq!("poem.txt", record["data"]["path"]);
+        assert_eq!(2, record["data"]["line_number"]);
+        assert_eq!(40, record["data"]["end_offset"]);
+        let starts: Vec<_> = record["data"]["submatches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["start"].as_u64().unwrap())
+            .collect();
+        assert_eq!(vec![5, 9, 11], starts);
+        assert_eq!("o", record["data"]["submatches"][0]["pattern"]);
+        assert!(printer.record(&Event::Break).is_none());
+    }
+}
@@ -0,0 +1,263 @@
+mod json;
+
+pub use json::{JsonPrinter, Summary};
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::replace::Replacer;
+use crate::search::{Event, Line, Matcher, Region};
+use crate::Config;
+
+/// When to highlight output with ANSI colors (`--color=WHEN`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal, `TERM` is not `dumb` and `NO_COLOR`
+    /// is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none()
+                    && env::var("TERM").map_or(true, |term| term != "dumb")
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("invalid value '{}' for '--color': expected always, never or auto", s)),
+        }
+    }
+}
+
+/// SGR codes for each part of the output, in the `GREP_COLORS` style used
+/// by `MINIGREP_COLORS`, e.g. `mt=01;31:fn=35:ln=32:bn=32:se=36`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// `mt`: the matched text.
+    pub matched: String,
+    /// `fn`: file names.
+    pub path: String,
+    /// `ln`: line numbers.
+    pub line_number: String,
+    /// `bn`: byte offsets.
+    pub byte_offset: String,
+    /// `se`: separators between fields and groups.
+    pub separator: String,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            matched: "01;31".to_string(),
+            path: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    /// The defaults overridden by `MINIGREP_COLORS`, if it is set.
+    pub fn from_env() -> Colors {
+        match env::var("MINIGREP_COLORS") {
+            Ok(spec) => Colors::parse(&spec),
+            Err(_) => Colors::default(),
+        }
+    }
+
+    /// Applies a `key=value:key=value` spec on top of the defaults. Unknown
+    /// keys are ignored, as grep does; an empty value turns that part off.
+    pub fn parse(spec: &str) -> Colors {
+        let mut colors = Colors::default();
+        for (key, value) in spec.split(':').filter_map(|entry| entry.split_once('=')) {
+            let slot = match key {
+                "mt" => &mut colors.matched,
+                "fn" => &mut colors.path,
+                "ln" => &mut colors.line_number,
+                "bn" => &mut colors.byte_offset,
+                "se" => &mut colors.separator,
+                _ => continue,
+            };
+            *slot = value.to_string();
+        }
+        colors
+    }
+}
+
+/// Prints search results for one input the way grep does:
+/// `name:number:offset:text` for selected lines, `name-number-offset-text`
+/// for context lines and `--` between groups that are not adjacent. With
+/// `--replace` the matches in selected lines are replaced, and the
+/// replacements are what gets highlighted.
+pub struct Printer<'a> {
+    config: &'a Config,
+    matcher: &'a Matcher,
+    colors: Option<&'a Colors>,
+    name: Option<&'a str>,
+}
+
+impl<'a> Printer<'a> {
+    /// `name` is printed in front of every line when it is given; `colors`
+    /// turns highlighting on.
+    pub fn new(
+        config: &'a Config,
+        matcher: &'a Matcher,
+        colors: Option<&'a Colors>,
+        name: Option<&'a str>,
+    ) -> Printer<'a> {
+        Printer {
+            config,
+            matcher,
+            colors,
+            name,
+        }
+    }
+
+    pub fn event(&self, out: &mut dyn Write, event: &Event) -> io::Result<()> {
+        match event {
+            Event::Match(m) => self.line(out, &m.line, ':', m.span.as_ref()),
+            Event::Context(line) => self.line(out, line, '-', None),
+            Event::Break => writeln!(out, "{}", self.paint(|c| &c.separator, "--")),
+        }
+    }
+
+    pub fn count(&self, out: &mut dyn Write, count: usize) -> io::Result<()> {
+        match self.name {
+            Some(name) => writeln!(
+                out,
+                "{}{}{}",
+                self.paint(|c| &c.path, name),
+                self.paint(|c| &c.separator, ":"),
+                count
+            ),
+            None => writeln!(out, "{}", count),
+        }
+    }
+
+    /// Prints `name` on its own, for `-l` and `-L`.
+    pub fn path(&self, out: &mut dyn Write, name: &str) -> io::Result<()> {
+        writeln!(out, "{}", self.paint(|c| &c.path, name))
+    }
+
+    /// Says that the binary file `name` matches, in place of its lines.
+    pub fn binary(&self, out: &mut dyn Write, name: &str) -> io::Result<()> {
+        writeln!(out, "Binary file {} matches", self.paint(|c| &c.path, name))
+    }
+
+    /// Prints a region a multiline search selected, after the numbers of
+    /// its first and last lines, which are always shown: the region may
+    /// span lines, so there is nothing else to tell where it ends.
+    pub fn region(&self, out: &mut dyn Write, region: &Region) -> io::Result<()> {
+        let numbers = match region.last_number {
+            last if last > region.line.number => format!("{}-{}", region.line.number, last),
+            _ => region.line.number.to_string(),
+        };
+        self.record(out, &region.line, Some(numbers), ':', Some(&region.span))
+    }
+
+    /// `span` is the first match in `line`, for selected lines that match.
+    fn line(&self, out: &mut dyn Write, line: &Line, separator: char, span: Option<&Range<usize>>) -> io::Result<()> {
+        let number = self.config.line_number.then(|| line.number.to_string());
+        self.record(out, line, number, separator, span)
+    }
+
+    /// Prints `line` after its name, `number` and offset, as asked for.
+    fn record(
+        &self,
+        out: &mut dyn Write,
+        line: &Line,
+        number: Option<String>,
+        separator: char,
+        span: Option<&Range<usize>>,
+    ) -> io::Result<()> {
+        let separator = separator.to_string();
+        let mut buf = String::new();
+        if let Some(name) = self.name {
+            buf.push_str(&self.paint(|c| &c.path, name));
+            buf.push_str(&self.paint(|c| &c.separator, &separator));
+        }
+        if let Some(number) = number {
+            buf.push_str(&self.paint(|c| &c.line_number, &number));
+            buf.push_str(&self.paint(|c| &c.separator, &separator));
+        }
+        if self.config.byte_offset {
+            buf.push_str(&self.paint(|c| &c.byte_offset, &line.offset.to_string()));
+            buf.push_str(&self.paint(|c| &c.separator, &separator));
+        }
+        if let Some(span) = span.filter(|_| self.config.show_pattern) {
+            buf.push_str(self.matcher.query_of(line.text, span));
+            buf.push_str(&self.paint(|c| &c.separator, &separator));
+        }
+
+        let replaced;
+        let (text, highlights): (&str, Vec<Range<usize>>) = match (span, &self.config.replace) {
+            (Some(_), Some(template)) => {
+                let (text, spans) = Replacer::new(self.matcher, template).line(line.text);
+                replaced = text;
+                (&replaced, spans)
+            }
+            (Some(_), None) if self.colors.is_some() => (line.text, self.matcher.find_iter(line.text).collect()),
+            _ => (line.text, Vec::new()),
+        };
+
+        if self.colors.is_some() {
+            let mut last = 0;
+            for span in highlights.into_iter().filter(|span| !span.is_empty()) {
+                buf.push_str(&text[last..span.start]);
+                buf.push_str(&self.paint(|c| &c.matched, &text[span.clone()]));
+                last = span.end;
+            }
+            buf.push_str(&text[last..]);
+        } else {
+            buf.push_str(text);
+        }
+        writeln!(out, "{}", buf)
+    }
+
+    fn paint(&self, code: fn(&Colors) -> &String, text: &str) -> String {
+        match self.colors.map(code) {
+            Some(code) if !code.is_empty() => format!("\x1b[{}m{}\x1b[0m", code, text),
+            _ => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_spec_overrides_defaults() {
+        let colors = Colors::parse("mt=01;32:fn=:xx=1:garbage");
+        assert_eq!("01;32", colors.matched);
+        assert_eq!("", colors.path);
+        assert_eq!(Colors::default().line_number, colors.line_number);
+    }
+
+    #[test]
+    fn color_choice_from_str() {
+        assert_eq!(Ok(ColorChoice::Always), "always".parse());
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+}
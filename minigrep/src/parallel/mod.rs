@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// One worker per available CPU, or one if that cannot be determined.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Runs `work` over `items` on a pool of `threads` workers and hands each
+/// result to `done` on the calling thread, in the order of `items`.
+///
+/// Workers pull the next item from a shared counter, so a slow item only
+/// holds up its own worker. Results that finish early wait in a reorder
+/// buffer until every item before them has been handed to `done`. When
+/// `done` breaks, the remaining items are skipped.
+pub fn for_each_ordered<T, R, W, D>(items: &[T], threads: usize, work: W, mut done: D)
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T) -> R + Sync,
+    D: FnMut(&T, R) -> bool,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        for item in items {
+            if !done(item, work(item)) {
+                return;
+            }
+        }
+        return;
+    }
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else { break };
+                if sender.send((index, work(item))).is_err() {
+                    // the receiver gave up, nobody wants more results
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (index, result) in receiver.iter() {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&expected) {
+                if !done(&items[expected], result) {
+                    // stop handing out work; running items still finish
+                    next.store(items.len(), Ordering::Relaxed);
+                    return;
+                }
+                expected += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_arrive_in_input_order() {
+        let items: Vec<u64> = (0..32).collect();
+        let mut seen = Vec::new();
+        for_each_ordered(
+            &items,
+            4,
+            |item| {
+                // make early items finish last
+                thread::sleep(Duration::from_millis(32 - item));
+                item * 2
+            },
+            |item, result| {
+                seen.push((*item, result));
+                true
+            },
+        );
+        assert_eq!(items.iter().map(|item| (*item, item * 2)).collect::<Vec<_>>(), seen);
+    }
+
+    #[test]
+    fn done_can_stop_early() {
+        let items: Vec<u64> = (0..100).collect();
+        let mut seen = 0;
+        for_each_ordered(&items, 3, |item| *item, |_, _| {
+            seen += 1;
+            seen < 5
+        });
+        assert_eq!(5, seen);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::io;
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
+
+use crate::search::{self, Event, Matcher, SearchOptions};
+
+/// Substitutes a template for every match of a [`Matcher`].
+///
+/// In the template `$0` stands for the whole match and `$1`, `$2`, ... or
+/// `$name` for the capture groups of a regular expression; braces delimit a
+/// name, as in `${1}st`, and `$$` is a literal `$`. A group that did not
+/// take part in the match, or does not exist, expands to nothing, so for a
+/// literal query only `$0` is useful.
+#[derive(Debug, Clone, Copy)]
+pub struct Replacer<'a> {
+    matcher: &'a Matcher,
+    template: &'a str,
+}
+
+impl<'a> Replacer<'a> {
+    pub fn new(matcher: &'a Matcher, template: &'a str) -> Replacer<'a> {
+        Replacer { matcher, template }
+    }
+
+    /// `line` with every match replaced, and where the replacements ended up
+    /// in the new line.
+    pub fn line(&self, line: &str) -> (String, Vec<Range<usize>>) {
+        let mut replaced = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut last = 0;
+        for span in self.matcher.find_iter(line) {
+            replaced.push_str(&line[last..span.start]);
+            let start = replaced.len();
+            match self.matcher.regex_of(line, &span).and_then(|re| re.captures_at(line, span.start)) {
+                Some(captures) => captures.expand(self.template, &mut replaced),
+                None => expand_literal(self.template, &line[span.clone()], &mut replaced),
+            }
+            spans.push(start..replaced.len());
+            last = span.end;
+        }
+        replaced.push_str(&line[last..]);
+        (replaced, spans)
+    }
+
+    /// Replaces the matches on the lines a search with `options` selects in
+    /// the file at `path`, and writes it back with
+    /// [`crate::io::write_with_backup`] if anything changed. Line
+    /// terminators and the lines that are not selected are kept byte for
+    /// byte. Returns the number of lines that changed.
+    ///
+    /// Only UTF-8 text is rewritten: a file that is not valid UTF-8 or
+    /// contains a NUL byte is an `InvalidData` error, as decoding it and
+    /// writing it back would not preserve the rest of it.
+    pub fn rewrite(&self, path: &Path, options: &SearchOptions) -> io::Result<usize> {
+        let contents = fs::read_to_string(path)?;
+        if crate::io::is_binary(&mut contents.as_bytes())? {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file, not rewritten"));
+        }
+        let options = SearchOptions {
+            before_context: 0,
+            after_context: 0,
+            ..*options
+        };
+
+        let mut rewritten = String::with_capacity(contents.len());
+        let mut copied = 0;
+        let mut changed = 0;
+        search::search_reader(self.matcher, &options, contents.as_bytes(), |event| {
+            // inverted searches select lines without matches to replace
+            if let Event::Match(m @ search::Match { span: Some(_), .. }) = event {
+                let (line, _) = self.line(m.line.text);
+                if line != m.line.text {
+                    rewritten.push_str(&contents[copied..m.line.offset]);
+                    rewritten.push_str(&line);
+                    copied = m.line.offset + m.line.text.len();
+                    changed += 1;
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        if changed > 0 {
+            rewritten.push_str(&contents[copied..]);
+            crate::io::write_with_backup(path, rewritten.as_bytes())?;
+        }
+        Ok(changed)
+    }
+}
+
+/// Expands `template` for a match that has no capture groups besides the
+/// match itself, following the syntax of `regex::Captures::expand`.
+fn expand_literal(template: &str, matched: &str, dst: &mut String) {
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        dst.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            dst.push('$');
+            rest = after;
+            continue;
+        }
+
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(close) => (&braced[..close], &braced[close + 1..]),
+                None => ("", rest),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        if name.is_empty() {
+            // not a reference after all
+            dst.push('$');
+            continue;
+        }
+        if name == "0" {
+            dst.push_str(matched);
+        }
+        rest = after;
+    }
+    dst.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search::MatchOptions;
+
+    fn replace(query: &str, regex: bool, template: &str, line: &str) -> String {
+        let matcher = Matcher::new(query, MatchOptions { regex, ..MatchOptions::default() }).unwrap();
+        Replacer::new(&matcher, template).line(line).0
+    }
+
+    #[test]
+    fn regex_templates_use_capture_groups() {
+        assert_eq!("b=a, d=c", replace(r"(\w)=(\w)", true, "$2=$1", "a=b, c=d"));
+        assert_eq!("1st 2st", replace(r"(?P<n>\d)", true, "${n}st", "1 2"));
+        assert_eq!("$x", replace("a", true, "$$x", "a"));
+    }
+
+    #[test]
+    fn literal_templates_only_know_the_whole_match() {
+        assert_eq!("[fast], [fast]", replace("fast", false, "[$0]", "fast, fast"));
+        assert_eq!("[], $ ${0", replace("fast", false, "[$1], $ ${0", "fast"));
+        assert_eq!("$fast$", replace("fast", false, "$$${0}$$", "fast"));
+    }
+
+    #[test]
+    fn several_patterns_keep_their_own_groups() {
+        let options = MatchOptions {
+            regex: true,
+            ..MatchOptions::default()
+        };
+        let matcher = Matcher::any(&[r"(\d+)px", r"(\d+)em"], options).unwrap();
+        let (line, spans) = Replacer::new(&matcher, "$1").line("12px 3em");
+        assert_eq!("12 3", line);
+        assert_eq!(vec![0..2, 3..4], spans);
+    }
+
+    #[test]
+    fn rewrite_keeps_everything_but_the_matches() {
+        let path = std::env::temp_dir().join(format!("minigrep-rewrite-{}.txt", std::process::id()));
+        fs::write(&path, "one fish\r\ntwo fish\nred\n").unwrap();
+
+        let matcher = Matcher::new("fish", MatchOptions::default()).unwrap();
+        let options = SearchOptions {
+            max_count: Some(1),
+            ..SearchOptions::default()
+        };
+        assert_eq!(1, Replacer::new(&matcher, "cat").rewrite(&path, &options).unwrap());
+        assert_eq!("one cat\r\ntwo fish\nred\n", fs::read_to_string(&path).unwrap());
+
+        let backup = crate::io::backup_path(&path);
+        assert_eq!("one fish\r\ntwo fish\nred\n", fs::read_to_string(&backup).unwrap());
+
+        // nothing changes, so nothing is written
+        assert_eq!(0, Replacer::new(&matcher, "$0").rewrite(&path, &options).unwrap());
+
+        fs::write(&path, "fish\0").unwrap();
+        let err = Replacer::new(&matcher, "cat").rewrite(&path, &options).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        fs::remove_file(path).unwrap();
+        fs::remove_file(backup).unwrap();
+    }
+}
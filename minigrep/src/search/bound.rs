@@ -0,0 +1,166 @@
+use std::ops::Range;
+
+use regex_syntax::is_word_character;
+
+use super::{spans, Matcher};
+
+/// Where a match may start and end, beyond containing the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// Neither the character before a match nor the one after it may be a
+    /// word character: a Unicode letter, digit or mark, or a connector such
+    /// as `_`, the same characters as `\w` in a regular expression.
+    Word,
+    /// A match has to cover the whole line.
+    Line,
+}
+
+impl Bound {
+    /// Whether the match `span` in `line` respects the bound.
+    pub fn admits(self, line: &str, span: &Range<usize>) -> bool {
+        match self {
+            Bound::Word => {
+                let before = line[..span.start].chars().next_back();
+                let after = line[span.end..].chars().next();
+                !before.is_some_and(is_word_character) && !after.is_some_and(is_word_character)
+            }
+            Bound::Line => span.start == 0 && span.end == line.len(),
+        }
+    }
+
+    /// Wraps a regular expression so that the regex engine enforces the
+    /// bound itself.
+    pub fn wrap(self, pattern: &str) -> String {
+        match self {
+            Bound::Word => format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern),
+            Bound::Line => format!("^(?:{})$", pattern),
+        }
+    }
+}
+
+/// A literal matcher that only reports the matches a [`Bound`] admits.
+///
+/// Candidates come from the inner matcher; one that is rejected does not
+/// end the search, since another occurrence may start inside it, as `ab`
+/// does in `aab` when looking for the word `ab`.
+///
+/// A set of several literals only reports the longest of the queries that
+/// match at a position, so each of them is also kept on its own, to try
+/// the shorter ones where the longest is rejected: `foo` is a word in
+/// `foo-bar`, though `foo-b` is not.
+#[derive(Debug)]
+pub struct Bounded {
+    inner: Box<Matcher>,
+    bound: Bound,
+    each: Vec<Matcher>,
+}
+
+impl Bounded {
+    pub fn new(inner: Matcher, bound: Bound) -> Bounded {
+        Bounded {
+            inner: Box::new(inner),
+            bound,
+            each: Vec::new(),
+        }
+    }
+
+    /// Also tries `each` of the queries of a set on its own.
+    pub fn with_each(mut self, each: Vec<Matcher>) -> Bounded {
+        self.each = each;
+        self
+    }
+
+    /// The byte range of the first admitted match in `line` starting at or
+    /// after byte `from`, which must be a character boundary.
+    pub fn find_at(&self, line: &str, mut from: usize) -> Option<Range<usize>> {
+        loop {
+            let span = self.inner.find_at(line, from)?;
+            if self.bound.admits(line, &span) {
+                return Some(span);
+            }
+            if let Some(shorter) = self.longest_at(line, span.start) {
+                return Some(shorter);
+            }
+            if self.bound == Bound::Line {
+                // every later candidate starts after the start of the line
+                return None;
+            }
+            from = span.start + line[span.start..].chars().next()?.len_utf8();
+        }
+    }
+
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        spans(line, move |from| self.find_at(line, from))
+    }
+
+    /// The query that produced `span`, a match found in `line`.
+    pub fn query_of(&self, line: &str, span: &Range<usize>) -> &str {
+        match self.each.iter().find(|query| query.find_at(line, span.start).as_ref() == Some(span)) {
+            Some(query) => query.query_of(line, span),
+            None => self.inner.query_of(line, span),
+        }
+    }
+
+    /// The longest admitted match of any single query that starts at
+    /// `start`.
+    fn longest_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.each
+            .iter()
+            .filter_map(|query| query.find_at(line, start))
+            .filter(|span| span.start == start && self.bound.admits(line, span))
+            .max_by_key(|span| span.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search::MatchOptions;
+
+    fn words(query: &str) -> Bounded {
+        Bounded::new(Matcher::new(query, MatchOptions::default()).unwrap(), Bound::Word)
+    }
+
+    #[test]
+    fn words_need_non_word_characters_around_them() {
+        assert_eq!(None, words("duct").find_at("safe, fast, productive.", 0));
+        assert_eq!(Some(6..10), words("fast").find_at("safe, fast, productive.", 0));
+        // letters, digits and `_` in any script are word characters
+        assert_eq!(None, words("id").find_at("user_id idé id7 ид", 0));
+        assert_eq!(vec![3..6], words("x-x").find_iter("ax-x-x").collect::<Vec<_>>());
+        // a query that starts with a non-word character still needs one before it
+        assert_eq!(Some(6..10), words("@foo").find_at("a@foo @foo", 0));
+    }
+
+    #[test]
+    fn shorter_queries_are_tried_where_the_longest_is_no_word() {
+        for ignore_case in [false, true] {
+            let options = MatchOptions {
+                ignore_case,
+                whole_word: true,
+                ..MatchOptions::default()
+            };
+            let matcher = Matcher::any(&["foo", "foo-b"], options).unwrap();
+            assert_eq!(Some(0..3), matcher.find("foo-bar"));
+            assert_eq!("foo", matcher.query_of("foo-bar", &(0..3)));
+            assert_eq!(Some(0..5), matcher.find("foo-b ar"));
+            assert_eq!("foo-b", matcher.query_of("foo-b ar", &(0..5)));
+        }
+    }
+
+    #[test]
+    fn lines_are_matched_whole() {
+        let line = Bounded::new(Matcher::new("Pick three.", MatchOptions::default()).unwrap(), Bound::Line);
+        assert_eq!(Some(0..11), line.find_at("Pick three.", 0));
+        assert_eq!(None, line.find_at("Pick three. Now", 0));
+    }
+
+    #[test]
+    fn regexes_are_wrapped() {
+        let regex = regex::Regex::new(&Bound::Word.wrap("@fo+")).unwrap();
+        assert_eq!(vec!["@foo"], regex.find_iter("a@foo @foo").map(|m| m.as_str()).collect::<Vec<_>>());
+        let regex = regex::Regex::new(&Bound::Line.wrap("a|b")).unwrap();
+        assert!(regex.is_match("b") && !regex.is_match("ab"));
+    }
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::ops::ControlFlow;
+
+use memchr::{memchr, memchr_iter, memrchr};
+
+use super::{Event, Line, Matcher, Selector};
+
+/// How many bytes are read at a time; a buffer only grows beyond this to
+/// hold a single longer line.
+pub(super) const CAPACITY: usize = 64 * 1024;
+
+/// Searches `reader` a buffer of whole lines at a time. The matcher runs
+/// over the entire buffer to find the next candidate, and only then are the
+/// boundaries of the line it sits on resolved; the lines skipped on the way
+/// are counted, not split, except for the last `before_context` of them.
+///
+/// `matcher` must never match across a line break.
+pub(super) fn search<R, F>(matcher: &Matcher, mut reader: R, capacity: usize, selector: &mut Selector<F>) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    let mut buf = vec![0; capacity];
+    let mut len = 0;
+    let mut number = 1;
+    let mut offset = 0;
+
+    loop {
+        if len == buf.len() {
+            buf.resize(buf.len() * 2, 0);
+        }
+        let read = match reader.read(&mut buf[len..]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let scanned = len;
+        len += read;
+
+        // search up to the end of the last complete line, or everything
+        // that is left once the input ends
+        let end = if read == 0 {
+            len
+        } else {
+            match memrchr(b'\n', &buf[scanned..len]) {
+                Some(newline) => scanned + newline + 1,
+                None => continue,
+            }
+        };
+
+        let flow = search_chunk(matcher, &buf[..end], &mut number, offset, selector)?;
+        if flow.is_break() {
+            return Ok(());
+        }
+        offset += end;
+
+        buf.copy_within(end..len, 0);
+        len -= end;
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Searches `bytes` where they are, as [`search`] does a buffer at a time.
+/// They are still taken about `capacity` bytes of whole lines at a time,
+/// so that each piece is checked for UTF-8 and searched while it is in the
+/// cache.
+pub(super) fn search_slice<F>(matcher: &Matcher, bytes: &[u8], capacity: usize, selector: &mut Selector<F>) -> io::Result<()>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    let mut number = 1;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        let end = match rest.get(capacity..).and_then(|tail| memchr(b'\n', tail)) {
+            Some(newline) => capacity + newline + 1,
+            None => rest.len(),
+        };
+        if search_chunk(matcher, &rest[..end], &mut number, offset, selector)?.is_break() {
+            break;
+        }
+        offset += end;
+    }
+    Ok(())
+}
+
+/// Searches `chunk`, whole lines that start at byte `offset` of the input,
+/// and moves `number` on past them.
+fn search_chunk<F>(
+    matcher: &Matcher,
+    chunk: &[u8],
+    number: &mut usize,
+    offset: usize,
+    selector: &mut Selector<F>,
+) -> io::Result<ControlFlow<()>>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    let flow = match crate::io::text(chunk) {
+        Cow::Borrowed(text) => {
+            let mut lines = Lines {
+                text,
+                number: *number,
+                offset,
+            };
+            let flow = search_lines(matcher, &mut lines, selector);
+            *number = lines.number;
+            flow
+        }
+        // decoding shifted the bytes around, so go line by line to keep
+        // offsets into the input
+        Cow::Owned(_) => {
+            let mut flow = ControlFlow::Continue(());
+            crate::io::for_each_line(chunk, |n, o, text| {
+                flow = selector.line(Line {
+                    number: *number + n - 1,
+                    offset: offset + o,
+                    text,
+                });
+                flow
+            })?;
+            *number += memchr_iter(b'\n', chunk).count();
+            flow
+        }
+    };
+    Ok(flow)
+}
+
+/// A buffer of whole lines, consumed from the front.
+struct Lines<'t> {
+    text: &'t str,
+    /// The number of the first line left in `text`.
+    number: usize,
+    /// The offset of `text` within the input.
+    offset: usize,
+}
+
+impl<'t> Lines<'t> {
+    /// Splits off the first line.
+    fn next(&mut self) -> Line<'t> {
+        let end = memchr(b'\n', self.text.as_bytes()).map_or(self.text.len(), |newline| newline + 1);
+        let (raw, rest) = self.text.split_at(end);
+        let text = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = Line {
+            number: self.number,
+            offset: self.offset,
+            text: text.strip_suffix('\r').unwrap_or(text),
+        };
+        self.text = rest;
+        self.number += 1;
+        self.offset += end;
+        line
+    }
+
+    /// Drops the lines before byte `to`, a line start, except for the last
+    /// `keep` of them.
+    fn skip(&mut self, to: usize, keep: usize) {
+        let bytes = self.text.as_bytes();
+        let mut start = to;
+        for _ in 0..keep {
+            if start == 0 {
+                return;
+            }
+            start = memrchr(b'\n', &bytes[..start - 1]).map_or(0, |newline| newline + 1);
+        }
+        self.number += memchr_iter(b'\n', &bytes[..start]).count();
+        self.offset += start;
+        self.text = &self.text[start..];
+    }
+}
+
+fn search_lines<F>(matcher: &Matcher, lines: &mut Lines, selector: &mut Selector<F>) -> ControlFlow<()>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    while !lines.text.is_empty() {
+        if selector.needs_next_line() {
+            selector.line(lines.next())?;
+            continue;
+        }
+
+        // the lines before the candidate's do not match; only the last
+        // `before_context` of them can still be reported
+        let bytes = lines.text.as_bytes();
+        let candidate = match matcher.find(lines.text) {
+            Some(span) => memrchr(b'\n', &bytes[..span.start]).map_or(0, |newline| newline + 1),
+            None => bytes.len(),
+        };
+        let rest = bytes.len() - candidate;
+        lines.skip(candidate, selector.options.before_context);
+        while lines.text.len() > rest {
+            selector.line(lines.next())?;
+        }
+        if !lines.text.is_empty() {
+            selector.line(lines.next())?;
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search::{search_reader, MatchOptions, SearchOptions};
+
+    const INPUT: &str = "a\nmatch 1\nb\nc\nd\r\ne\nmatch 2 match\nf\nmatch 3\ng\nmatch 4";
+
+    fn render(events: &mut Vec<String>, event: Event) -> ControlFlow<()> {
+        events.push(match event {
+            Event::Match(m) => format!("{}:{}:{}", m.line.number, m.line.offset, m.line.text),
+            Event::Context(line) => format!("{}-{}-{}", line.number, line.offset, line.text),
+            Event::Break => "--".to_string(),
+        });
+        ControlFlow::Continue(())
+    }
+
+    fn buffered(matcher: &Matcher, options: &SearchOptions, capacity: usize) -> io::Result<Vec<String>> {
+        let mut events = Vec::new();
+        let mut selector = Selector::new(matcher, options, |event| render(&mut events, event));
+        search(matcher, io::Cursor::new(INPUT), capacity, &mut selector)?;
+        Ok(events)
+    }
+
+    fn line_by_line(options: &SearchOptions) -> Vec<String> {
+        let regex = Matcher::new("match", MatchOptions { regex: true, ..MatchOptions::default() }).unwrap();
+        let mut events = Vec::new();
+        search_reader(&regex, options, io::Cursor::new(INPUT), |event| render(&mut events, event)).unwrap();
+        events
+    }
+
+    #[test]
+    fn agrees_with_the_line_by_line_search() {
+        let literal = Matcher::new("match", MatchOptions::default()).unwrap();
+        for (before_context, after_context, max_count) in [(0, 0, None), (1, 1, None), (2, 0, Some(2)), (0, 3, Some(1)), (9, 9, None)] {
+            let options = SearchOptions {
+                before_context,
+                after_context,
+                max_count,
+                ..SearchOptions::default()
+            };
+            // small buffers put chunk boundaries everywhere and make them grow
+            for capacity in [1, 2, 5, 16, CAPACITY] {
+                assert_eq!(line_by_line(&options), buffered(&literal, &options, capacity).unwrap(), "{:?}", options);
+            }
+        }
+    }
+
+    #[test]
+    fn slices_are_searched_like_readers() {
+        let literal = Matcher::new("match", MatchOptions::default()).unwrap();
+        let options = SearchOptions {
+            before_context: 1,
+            after_context: 1,
+            ..SearchOptions::default()
+        };
+        let mut events = Vec::new();
+        let mut selector = Selector::new(&literal, &options, |event| render(&mut events, event));
+        search_slice(&literal, INPUT.as_bytes(), CAPACITY, &mut selector).unwrap();
+        assert_eq!(buffered(&literal, &options, CAPACITY).unwrap(), events);
+
+        for capacity in [1, 5, 16] {
+            let mut chunked = Vec::new();
+            let mut selector = Selector::new(&literal, &options, |event| render(&mut chunked, event));
+            search_slice(&literal, INPUT.as_bytes(), capacity, &mut selector).unwrap();
+            assert_eq!(events, chunked);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_keeps_offsets_into_the_input() {
+        let literal = Matcher::new("match", MatchOptions::default()).unwrap();
+        let options = SearchOptions::default();
+        let mut events = Vec::new();
+        let mut selector = Selector::new(&literal, &options, |event| render(&mut events, event));
+        search(&literal, &b"caf\xe9\nmatch \xff\nmatch\n"[..], CAPACITY, &mut selector).unwrap();
+        assert_eq!(vec!["2:5:match \u{fffd}", "3:13:match"], events);
+    }
+}
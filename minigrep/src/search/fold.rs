@@ -0,0 +1,293 @@
+use std::iter;
+use std::ops::Range;
+
+use caseless::Caseless;
+use memchr::memchr2;
+
+use super::spans;
+
+/// A literal query matched under Unicode full case folding (the C and F
+/// mappings of `CaseFolding.txt`), so `STRASSE` finds `straße`, `ﬁle` finds
+/// `FILE` and `ΣΟΦΟΣ` finds `σοφος`.
+///
+/// Only the query is folded up front. ASCII lines, by far the most common
+/// kind, are searched with a plain ASCII-case-insensitive scan; other lines
+/// are folded one character at a time and fed through a Knuth-Morris-Pratt
+/// automaton, so every character is folded once. Nothing is allocated per
+/// line. Folding is locale independent: `İ` folds to `i̇` (`i` followed by a
+/// combining dot) and `I` to `i`, as the Unicode default prescribes, rather
+/// than following the Turkish rules. A match always starts and ends on a
+/// character boundary of the line, so `s` does not match half of `ß`.
+#[derive(Debug, Clone)]
+pub struct FoldedLiteral {
+    query: String,
+    folded: Vec<char>,
+    /// `fallback[i]` is the length of the longest proper prefix of
+    /// `folded[..=i]` that is also a suffix of it.
+    fallback: Vec<usize>,
+    /// The folded query as bytes, if it is all ASCII. ASCII text folds to
+    /// ASCII, so without this an ASCII line can never match.
+    ascii: Option<Vec<u8>>,
+}
+
+impl FoldedLiteral {
+    pub fn new(query: &str) -> FoldedLiteral {
+        let folded: Vec<char> = query.chars().default_case_fold().collect();
+
+        let mut fallback = vec![0; folded.len()];
+        let mut len = 0;
+        for i in 1..folded.len() {
+            while len > 0 && folded[i] != folded[len] {
+                len = fallback[len - 1];
+            }
+            if folded[i] == folded[len] {
+                len += 1;
+            }
+            fallback[i] = len;
+        }
+
+        let ascii = folded.iter().all(char::is_ascii).then(|| folded.iter().map(|&c| c as u8).collect());
+
+        FoldedLiteral {
+            query: query.to_string(),
+            folded,
+            fallback,
+            ascii,
+        }
+    }
+
+    /// The query as it was given, before folding.
+    pub fn as_str(&self) -> &str {
+        &self.query
+    }
+
+    /// The byte range of the first match in `line` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        if self.folded.is_empty() {
+            return Some(from..from);
+        }
+
+        let rest = &line[from..];
+        if rest.is_ascii() {
+            let span = self.find_ascii(rest.as_bytes())?;
+            return Some(from + span.start..from + span.end);
+        }
+
+        let bytes = line.as_bytes();
+        let ascii_first = u8::try_from(self.folded[0]).ok().filter(u8::is_ascii);
+        let mut state = 0;
+        let mut end = from;
+        let mut ascii_until = from;
+
+        while end < bytes.len() {
+            if state == 0 && bytes[end].is_ascii() {
+                // nothing to resume: jump over the ASCII bytes that cannot
+                // start a match instead of feeding them to the automaton
+                if end >= ascii_until {
+                    ascii_until = bytes[end..].iter().position(|b| !b.is_ascii()).map_or(bytes.len(), |p| end + p);
+                }
+                let run = &bytes[end..ascii_until];
+                end += match ascii_first {
+                    Some(first) => memchr2(first, first.to_ascii_uppercase(), run).unwrap_or(run.len()),
+                    None => run.len(),
+                };
+                if end == bytes.len() {
+                    break;
+                }
+            }
+
+            let byte = bytes[end];
+            if byte.is_ascii() {
+                end += 1;
+                state = self.step(state, char::from(byte.to_ascii_lowercase()));
+            } else {
+                let c = line[end..].chars().next().expect("`end` is a character boundary");
+                end += c.len_utf8();
+                fold(c, |folded| state = self.step(state, folded));
+            }
+
+            // the folded query ends with the character before `end`; it is
+            // a match if it also starts at the beginning of a character
+            if state == self.folded.len() {
+                if let Some(start) = self.start_of_match(line, from, end) {
+                    return Some(start..end);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        spans(line, move |from| self.find_at(line, from))
+    }
+
+    /// Full case folding of ASCII text is ASCII lowercasing, so look for
+    /// the first byte of the query in either case and compare from there.
+    fn find_ascii(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let needle = self.ascii.as_deref()?;
+        let mut from = 0;
+        while let Some(found) = memchr2(needle[0], needle[0].to_ascii_uppercase(), &haystack[from..]) {
+            let start = from + found;
+            let candidate = haystack.get(start..start + needle.len())?;
+            if candidate.eq_ignore_ascii_case(needle) {
+                return Some(start..start + needle.len());
+            }
+            from = start + 1;
+        }
+        None
+    }
+
+    /// Advances the automaton by one folded character.
+    fn step(&self, mut state: usize, c: char) -> usize {
+        if state == self.folded.len() {
+            state = self.fallback[state - 1];
+        }
+        while state > 0 && self.folded[state] != c {
+            state = self.fallback[state - 1];
+        }
+        if self.folded[state] == c {
+            state += 1;
+        }
+        state
+    }
+
+    /// Walks back from `end` over as many characters as fold to the length
+    /// of the query, and returns where they start unless that is in the
+    /// middle of a character's folding.
+    fn start_of_match(&self, line: &str, from: usize, end: usize) -> Option<usize> {
+        let mut remaining = self.folded.len();
+        for (start, c) in line[from..end].char_indices().rev() {
+            let mut len = 0;
+            fold(c, |_| len += 1);
+            remaining = remaining.checked_sub(len)?;
+            if remaining == 0 {
+                return Some(from + start);
+            }
+        }
+        None
+    }
+}
+
+/// Calls `f` with every character `c` folds to.
+fn fold<F: FnMut(char)>(c: char, mut f: F) {
+    // ASCII folds to one ASCII character, skip the table lookup
+    if c.is_ascii() {
+        f(c.to_ascii_lowercase());
+    } else {
+        iter::once(c).default_case_fold().for_each(f);
+    }
+}
+
+/// Several literal queries compared under full case folding; of the
+/// matches that start at the same position the longest one wins.
+///
+/// Every query is looked for separately, so the cost grows with the number
+/// of queries.
+#[derive(Debug, Clone)]
+pub struct FoldedLiterals {
+    queries: Vec<FoldedLiteral>,
+}
+
+impl FoldedLiterals {
+    pub fn new<S: AsRef<str>>(queries: &[S]) -> FoldedLiterals {
+        let queries = queries.iter().map(|query| FoldedLiteral::new(query.as_ref())).collect();
+        FoldedLiterals { queries }
+    }
+
+    /// The byte range of the first match in `line` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        self.queries
+            .iter()
+            .filter_map(|query| query.find_at(line, from))
+            .min_by_key(|span| (span.start, usize::MAX - span.end))
+    }
+
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        spans(line, move |from| self.find_at(line, from))
+    }
+
+    /// The query that produced `span`, a match found in `line`.
+    pub fn query_of(&self, line: &str, span: &Range<usize>) -> &str {
+        let matched = |query: &&FoldedLiteral| query.find_at(line, span.start).as_ref() == Some(span);
+        self.queries.iter().find(matched).map_or("", FoldedLiteral::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, line: &str) -> Option<Range<usize>> {
+        FoldedLiteral::new(query).find_at(line, 0)
+    }
+
+    #[test]
+    fn folds_ascii() {
+        assert_eq!(Some(0..4), find("rUsT", "Rust:"));
+        assert_eq!(Some(1..5), find("rust", "Trust me."));
+        assert_eq!(None, find("rust", "Rus t"));
+    }
+
+    #[test]
+    fn full_folding_expands_characters() {
+        assert_eq!(Some(3..10), find("STRASSE", "in straße"));
+        assert_eq!(Some(3..10), find("straße", "in STRASSE"));
+        assert_eq!(Some(0..5), find("FILE", "ﬁle"));
+        // a match never ends in the middle of the folding of `ß`
+        assert_eq!(None, find("stras", "straße"));
+    }
+
+    #[test]
+    fn folds_greek_sigma_and_symbol_variants() {
+        assert_eq!(Some(0..10), find("ΣΟΦΟΣ", "σοφος"));
+        assert_eq!(Some(0..2), find("s", "ſ"));
+        assert_eq!(Some(0..3), find("k", "\u{212a}"));
+    }
+
+    #[test]
+    fn dotted_capital_i_uses_default_folding() {
+        assert_eq!(Some(0..3), find("İ", "i\u{307}"));
+        assert_eq!(Some(0..2), find("i\u{307}", "İ"));
+        assert_eq!(None, find("i", "İ"));
+        assert_eq!(None, find("ı", "I"));
+    }
+
+    #[test]
+    fn repeated_prefixes_are_not_skipped() {
+        assert_eq!(Some(2..5), find("AAB", "aaaab"));
+        assert_eq!(Some(3..7), find("abab", "abaABAB"));
+        assert_eq!(Some(2..5), find("sss", "ßßs"));
+    }
+
+    #[test]
+    fn ascii_lines_take_the_fast_path() {
+        assert_eq!(None, find("σοφος", "sofos SOFOS"));
+        assert_eq!(Some(10..17), find("Straße", "strase no STRASSE"));
+    }
+
+    #[test]
+    fn mixed_lines_skip_ascii_runs() {
+        assert_eq!(Some(12..17), find("K-7", "ΣΟΦΟΣ, \u{212a}-7"));
+        assert_eq!(Some(11..14), find("k-7", "ΣΟΦΟΣ k-7"));
+        assert_eq!(Some(0..10), find("σοφος", "ΣΟΦΟΣ k-7"));
+    }
+
+    #[test]
+    fn find_iter_reports_every_match() {
+        let query = FoldedLiteral::new("SS");
+        assert_eq!(vec![0..2, 3..5], query.find_iter("ßxSs").collect::<Vec<_>>());
+
+        let empty = FoldedLiteral::new("");
+        assert_eq!(vec![0..0, 2..2, 3..3], empty.find_iter("ßx").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn several_queries_prefer_the_leftmost_longest_match() {
+        let queries = FoldedLiterals::new(&["STRASSE", "s", "straße-7"]);
+        assert_eq!(vec![0..9, 10..11], queries.find_iter("Straße-7 s").collect::<Vec<_>>());
+        assert_eq!("straße-7", queries.query_of("Straße-7 s", &(0..9)));
+        assert_eq!("s", queries.query_of("Straße-7 s", &(10..11)));
+    }
+}
@@ -0,0 +1,171 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// Looks for substrings within a number of edits of any of several literal
+/// queries, where an edit inserts, deletes or substitutes one character, as
+/// in Levenshtein distance.
+///
+/// A line holds at most one match: the closest substring that ends first,
+/// taking in what follows it for as long as a substring with the same start
+/// costs no more edits. With `ignore_case` characters are compared by their
+/// lowercase forms, one character for one, so `ß` does not match `ss`.
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    queries: Vec<String>,
+    chars: Vec<Vec<char>>,
+    distance: usize,
+    ignore_case: bool,
+}
+
+impl Fuzzy {
+    pub fn new<S: AsRef<str>>(queries: &[S], distance: usize, ignore_case: bool) -> Fuzzy {
+        let queries: Vec<String> = queries.iter().map(|query| query.as_ref().to_string()).collect();
+        let chars = queries.iter().map(|query| query.chars().map(|c| fold(c, ignore_case)).collect()).collect();
+        Fuzzy {
+            queries,
+            chars,
+            distance,
+            ignore_case,
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// The byte range of the closest match in `line` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        self.chars
+            .iter()
+            .filter_map(|query| self.closest(query, line, from))
+            .min_by_key(|(edits, _)| *edits)
+            .map(|(_, span)| span)
+    }
+
+    /// The closest match in `line`, the only one there is.
+    pub fn find_iter(&self, line: &str) -> impl Iterator<Item = Range<usize>> {
+        self.find_at(line, 0).into_iter()
+    }
+
+    /// The query that produced `span`, a match found in `line`: the first
+    /// of those fewest edits away from it.
+    pub fn query_of(&self, line: &str, span: &Range<usize>) -> &str {
+        let found: Vec<char> = line[span.clone()].chars().map(|c| fold(c, self.ignore_case)).collect();
+        self.chars
+            .iter()
+            .position(|query| edits(query, &found) <= self.distance)
+            .map_or("", |index| &self.queries[index])
+    }
+
+    /// The fewest edits that turn `query` into a substring of `line` after
+    /// `from`, and the shortest such substring that ends first, extended to
+    /// the last one with the same start that costs no more edits.
+    ///
+    /// This is Sellers' algorithm: a column of edit distances between each
+    /// prefix of `query` and the best substring ending at the current
+    /// character, where starting anew costs nothing, so a match may begin
+    /// anywhere. Each cell also remembers where its substring starts.
+    fn closest(&self, query: &[char], line: &str, from: usize) -> Option<(usize, Range<usize>)> {
+        let mut column: Vec<(usize, usize)> = (0..=query.len()).map(|prefix| (prefix, from)).collect();
+        let mut closest = (query.len() <= self.distance).then_some((query.len(), from..from));
+        for (index, c) in line[from..].char_indices() {
+            let end = from + index + c.len_utf8();
+            let c = fold(c, self.ignore_case);
+            let mut diagonal = column[0];
+            column[0] = (0, end);
+            for prefix in 1..=query.len() {
+                let above = column[prefix];
+                let substitute = (diagonal.0 + usize::from(query[prefix - 1] != c), diagonal.1);
+                let insert = (above.0 + 1, above.1);
+                let delete = (column[prefix - 1].0 + 1, column[prefix - 1].1);
+                column[prefix] = [substitute, insert, delete]
+                    .into_iter()
+                    .min_by_key(|&(edits, start)| (edits, Reverse(start)))
+                    .unwrap();
+                diagonal = above;
+            }
+
+            // a match as close with the same start extends the closest one,
+            // so `colour` is found for `color` rather than `colo`
+            let (edits, start) = column[query.len()];
+            let closer = closest
+                .as_ref()
+                .is_none_or(|(fewest, span)| edits < *fewest || (edits == *fewest && start == span.start));
+            if edits <= self.distance && closer {
+                closest = Some((edits, start..end));
+            } else if closest.as_ref().is_some_and(|(fewest, _)| *fewest == 0) {
+                break;
+            }
+        }
+        closest
+    }
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edits(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(x != y)).min(above + 1).min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(queries: &[&str], distance: usize, line: &str) -> Option<String> {
+        let fuzzy = Fuzzy::new(queries, distance, false);
+        fuzzy.find_at(line, 0).map(|span| line[span].to_string())
+    }
+
+    #[test]
+    fn finds_substrings_within_the_distance() {
+        assert_eq!(Some("recieve".to_string()), find(&["receive"], 2, "we recieve data"));
+        assert_eq!(None, find(&["receive"], 1, "we recieve data"));
+        assert_eq!(Some("colr".to_string()), find(&["color"], 1, "the colr red"));
+        assert_eq!(Some("colour".to_string()), find(&["color"], 1, "the colour red"));
+        assert_eq!(Some("unïcode".to_string()), find(&["unicode"], 1, "the unïcode text"));
+        assert_eq!(Some(String::new()), find(&["ab"], 2, "xyz"));
+    }
+
+    #[test]
+    fn the_closest_match_wins() {
+        assert_eq!(Some("handler".to_string()), find(&["handler"], 2, "handlr, hndler and handler"));
+        assert_eq!(Some("handlr".to_string()), find(&["handler"], 2, "handlr, hndler"));
+        let fuzzy = Fuzzy::new(&["parse", "config"], 1, false);
+        let line = "parze the confg";
+        let span = fuzzy.find_at(line, 0).unwrap();
+        assert_eq!(("parze", "parse"), (&line[span.clone()], fuzzy.query_of(line, &span)));
+        assert_eq!(vec![span], fuzzy.find_iter(line).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn case_can_be_ignored() {
+        assert!(!Fuzzy::new(&["error"], 1, false).is_match("ERORR"));
+        assert!(Fuzzy::new(&["error"], 1, true).is_match("EROR"));
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(3, edits(&chars("kitten"), &chars("sitting")));
+        assert_eq!(0, edits(&chars(""), &chars("")));
+        assert_eq!(4, edits(&chars("rust"), &chars("")));
+    }
+}
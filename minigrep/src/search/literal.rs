@@ -0,0 +1,142 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+use std::str;
+
+use aho_corasick::{packed, AhoCorasick, BuildError, Input, MatchKind, Span};
+use memchr::memmem::Finder;
+
+use super::spans;
+
+/// A case-sensitive literal query.
+///
+/// The needle is preprocessed once, and every search then runs memchr's
+/// substring search: a vectorised scan for a rare byte of the needle finds
+/// candidates, which are verified with the Two-Way algorithm, so the search
+/// stays linear however the needle and the text repeat themselves.
+#[derive(Debug, Clone)]
+pub struct Literal {
+    finder: Finder<'static>,
+}
+
+impl Literal {
+    pub fn new(query: &str) -> Literal {
+        Literal {
+            finder: Finder::new(query).into_owned(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(self.finder.needle()).expect("the needle was built from a `str`")
+    }
+
+    /// The byte range of the first match in `haystack` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, haystack: &str, from: usize) -> Option<Range<usize>> {
+        let start = from + self.finder.find(&haystack.as_bytes()[from..])?;
+        Some(start..start + self.finder.needle().len())
+    }
+
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        spans(haystack, move |from| self.find_at(haystack, from))
+    }
+}
+
+/// Several case-sensitive literal queries searched for in a single pass,
+/// rather than one pass per query: with Teddy, a SIMD searcher for up to a
+/// few dozen needles, where the CPU supports it, and with an Aho-Corasick
+/// automaton otherwise.
+///
+/// Of the matches that start at the same position the longest one wins, as
+/// in grep, so `foo` and `foobar` report `foobar` in `foobar`. Both
+/// searchers prefer the query given first instead, so the queries are
+/// handed to them longest first.
+#[derive(Debug, Clone)]
+pub struct Literals {
+    /// The queries, longest first; the searchers report indexes into this.
+    queries: Vec<String>,
+    teddy: Option<packed::Searcher>,
+    automaton: AhoCorasick,
+}
+
+impl Literals {
+    pub fn new<S: AsRef<str>>(queries: &[S]) -> Result<Literals, BuildError> {
+        let mut queries: Vec<String> = queries.iter().map(|query| query.as_ref().to_string()).collect();
+        queries.sort_by_key(|query| Reverse(query.len()));
+
+        let teddy = packed::Config::new()
+            .match_kind(packed::MatchKind::LeftmostFirst)
+            .builder()
+            .extend(&queries)
+            .build();
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(&queries)?;
+        Ok(Literals {
+            queries,
+            teddy,
+            automaton,
+        })
+    }
+
+    /// The byte range of the first match in `haystack` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, haystack: &str, from: usize) -> Option<Range<usize>> {
+        self.search(haystack, from).map(|m| m.range())
+    }
+
+    /// The query that produced `span`, a match found in `haystack`.
+    pub fn query_of(&self, haystack: &str, span: &Range<usize>) -> &str {
+        let found = self.search(haystack, span.start).expect("`span` is a match");
+        &self.queries[found.pattern()]
+    }
+
+    fn search(&self, haystack: &str, from: usize) -> Option<aho_corasick::Match> {
+        match &self.teddy {
+            Some(teddy) => teddy.find_in(haystack, Span::from(from..haystack.len())),
+            None => self.automaton.find(Input::new(haystack).range(from..)),
+        }
+    }
+
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        spans(haystack, move |from| self.find_at(haystack, from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_finds_every_occurrence() {
+        let literal = Literal::new("ab");
+        assert_eq!("ab", literal.as_str());
+        assert_eq!(Some(4..6), literal.find_at("xxabab", 3));
+        assert_eq!(vec![2..4, 4..6], literal.find_iter("xxabab").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_queries_match_on_character_boundaries() {
+        let literal = Literal::new("");
+        assert_eq!(vec![0..0, 2..2, 3..3], literal.find_iter("ßx").collect::<Vec<_>>());
+
+        let literals = Literals::new(&["", "x"]).unwrap();
+        assert_eq!(vec![0..0, 2..3, 3..3], literals.find_iter("ßx").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn literals_prefer_the_leftmost_longest_match() {
+        let literals = Literals::new(&["foo", "foobar", "bar"]).unwrap();
+        // without SIMD support the automaton does all the work
+        let automaton = Literals {
+            teddy: None,
+            ..literals.clone()
+        };
+        for literals in [literals, automaton] {
+            assert_eq!(vec![0..6, 7..10], literals.find_iter("foobar bar").collect::<Vec<_>>());
+            assert_eq!(Some(4..7), literals.find_at("foo bar", 1));
+            assert_eq!("foobar", literals.query_of("foobar bar", &(0..6)));
+            assert_eq!("bar", literals.query_of("foobar bar", &(7..10)));
+        }
+        assert_eq!(None, Literals::new::<&str>(&[]).unwrap().find_at("foo", 0));
+    }
+}
@@ -1,25 +1,461 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::iter;
+use std::ops::{ControlFlow, Range};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::io::Input;
+
+mod bound;
+mod buffer;
+mod fold;
+mod fuzzy;
+mod literal;
+mod multiline;
+mod regexes;
+mod searcher;
+
+pub use bound::{Bound, Bounded};
+pub use fold::{FoldedLiteral, FoldedLiterals};
+pub use fuzzy::Fuzzy;
+pub use literal::{Literal, Literals};
+pub use multiline::{regions, Region};
+pub use regexes::Regexes;
+pub use searcher::{Searcher, Sink};
+
+/// How a query is interpreted when it is compiled into a [`Matcher`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchOptions {
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Only match whole words.
+    pub whole_word: bool,
+    /// Only match whole lines; wins over `whole_word`.
+    pub whole_line: bool,
+    /// Let matches span several lines, see [`regions`]. Every query is then
+    /// compiled into a regular expression, literal ones escaped, where `^`
+    /// and `$` match at line boundaries.
+    pub multiline: bool,
+    /// Match literal queries approximately, within this many edits, see
+    /// [`Fuzzy`]; `regex`, `whole_word` and `whole_line` are then ignored.
+    pub fuzzy: Option<usize>,
+}
+
+impl MatchOptions {
+    pub fn bound(&self) -> Option<Bound> {
+        if self.whole_line {
+            Some(Bound::Line)
+        } else if self.whole_word {
+            Some(Bound::Word)
+        } else {
+            None
+        }
+    }
+}
+
+/// A query compiled once up front and then tested against every line.
+#[derive(Debug)]
+pub enum Matcher {
+    Literal(Literal),
+    /// Several literal queries, looked for in a single pass.
+    Literals(Literals),
+    /// A literal query compared under full Unicode case folding.
+    FoldedLiteral(FoldedLiteral),
+    FoldedLiterals(FoldedLiterals),
+    /// A regular expression; with `ignore_case` it uses the regex engine's
+    /// simple case folding, so `ß` does not match `ss` there.
+    Regex(Regex),
+    /// Several regular expressions, or any with a [`Bound`].
+    Regexes(Regexes),
+    /// A literal matcher that only reports whole words or whole lines.
+    Bounded(Bounded),
+    /// Literal queries matched within a number of edits.
+    Fuzzy(Fuzzy),
+}
+
+impl Matcher {
+    pub fn new(query: &str, options: MatchOptions) -> Result<Matcher, regex::Error> {
+        Matcher::any(&[query], options)
+    }
+
+    /// A matcher for lines that match any of `queries`; without any
+    /// queries it matches nothing.
+    ///
+    /// A literal query that contains a line break can never match a single
+    /// line, so it is dropped; the matchers that remain never match across
+    /// lines, which lets [`search_reader`] search whole buffers for them.
+    /// With `multiline` this is not so, and nothing is dropped.
+    /// With `fuzzy` every query is kept as well, since edits can take out
+    /// the line break.
+    pub fn any<S: AsRef<str>>(queries: &[S], options: MatchOptions) -> Result<Matcher, regex::Error> {
+        if let Some(distance) = options.fuzzy {
+            return Ok(Matcher::Fuzzy(Fuzzy::new(queries, distance, options.ignore_case)));
+        }
+        if options.multiline {
+            return Regexes::new(queries, options).map(Matcher::Regexes);
+        }
+        if !options.regex || queries.is_empty() {
+            let queries: Vec<&str> = queries.iter().map(AsRef::as_ref).filter(|query| !query.contains('\n')).collect();
+            let matcher = match (queries.as_slice(), options.ignore_case) {
+                ([query], false) => Matcher::Literal(Literal::new(query)),
+                ([query], true) => Matcher::FoldedLiteral(FoldedLiteral::new(query)),
+                (queries, false) => {
+                    let literals = Literals::new(queries).map_err(|e| regex::Error::Syntax(e.to_string()))?;
+                    Matcher::Literals(literals)
+                }
+                (queries, true) => Matcher::FoldedLiterals(FoldedLiterals::new(queries)),
+            };
+            let each = |query: &&str| {
+                if options.ignore_case {
+                    Matcher::FoldedLiteral(FoldedLiteral::new(query))
+                } else {
+                    Matcher::Literal(Literal::new(query))
+                }
+            };
+            return Ok(match options.bound() {
+                Some(bound) if queries.len() > 1 => {
+                    Matcher::Bounded(Bounded::new(matcher, bound).with_each(queries.iter().map(each).collect()))
+                }
+                Some(bound) => Matcher::Bounded(Bounded::new(matcher, bound)),
+                None => matcher,
+            });
+        }
+
+        match queries {
+            [query] if options.bound().is_none() => RegexBuilder::new(query.as_ref())
+                .case_insensitive(options.ignore_case)
+                .build()
+                .map(Matcher::Regex),
+            queries => Regexes::new(queries, options).map(Matcher::Regexes),
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Regex(re) => re.is_match(line),
+            Matcher::Regexes(res) => res.is_match(line),
+            Matcher::Fuzzy(fuzzy) => fuzzy.is_match(line),
+            _ => self.find(line).is_some(),
+        }
+    }
+
+    /// The byte range of the first match in `line`, if any.
+    pub fn find(&self, line: &str) -> Option<Range<usize>> {
+        self.find_at(line, 0)
+    }
+
+    /// The byte range of the first match in `line` starting at or after
+    /// byte `from`, which must be a character boundary.
+    pub fn find_at(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        match self {
+            Matcher::Literal(query) => query.find_at(line, from),
+            Matcher::Literals(queries) => queries.find_at(line, from),
+            Matcher::FoldedLiteral(query) => query.find_at(line, from),
+            Matcher::FoldedLiterals(queries) => queries.find_at(line, from),
+            Matcher::Regex(re) => re.find_at(line, from).map(|m| m.range()),
+            Matcher::Regexes(res) => res.find_at(line, from),
+            Matcher::Bounded(bounded) => bounded.find_at(line, from),
+            Matcher::Fuzzy(fuzzy) => fuzzy.find_at(line, from),
+        }
+    }
+
+    /// The byte ranges of every non-overlapping match in `line`.
+    pub fn find_iter<'l>(&'l self, line: &'l str) -> Box<dyn Iterator<Item = Range<usize>> + 'l> {
+        match self {
+            Matcher::Literal(query) => Box::new(query.find_iter(line)),
+            Matcher::Literals(queries) => Box::new(queries.find_iter(line)),
+            Matcher::FoldedLiteral(query) => Box::new(query.find_iter(line)),
+            Matcher::FoldedLiterals(queries) => Box::new(queries.find_iter(line)),
+            Matcher::Regex(re) => Box::new(re.find_iter(line).map(|m| m.range())),
+            Matcher::Regexes(res) => Box::new(res.find_iter(line)),
+            Matcher::Bounded(bounded) => Box::new(bounded.find_iter(line)),
+            Matcher::Fuzzy(fuzzy) => Box::new(fuzzy.find_iter(line)),
+        }
+    }
+
+    /// The query that produced `span`, a match that [`Matcher::find`] or
+    /// [`Matcher::find_iter`] reported in `line`.
+    pub fn query_of(&self, line: &str, span: &Range<usize>) -> &str {
+        match self {
+            Matcher::Literal(query) => query.as_str(),
+            Matcher::Literals(queries) => queries.query_of(line, span),
+            Matcher::FoldedLiteral(query) => query.as_str(),
+            Matcher::FoldedLiterals(queries) => queries.query_of(line, span),
+            Matcher::Regex(re) => re.as_str(),
+            Matcher::Regexes(res) => res.query_of(line, span),
+            Matcher::Bounded(bounded) => bounded.query_of(line, span),
+            Matcher::Fuzzy(fuzzy) => fuzzy.query_of(line, span),
+        }
+    }
+
+    /// The regular expression that produced `span`, a match reported in
+    /// `line`, or `None` for literal queries, which have no capture groups.
+    pub fn regex_of(&self, line: &str, span: &Range<usize>) -> Option<&Regex> {
+        match self {
+            Matcher::Regex(re) => Some(re),
+            Matcher::Regexes(res) => Some(res.regex_of(line, span)),
+            _ => None,
+        }
+    }
+}
+
+/// Every non-overlapping match in `haystack`, given a search that finds the
+/// first match at or after a byte offset. An empty match steps over one
+/// character, so the next search neither loops nor splits a character.
+fn spans<'a, F>(haystack: &'a str, find_at: F) -> impl Iterator<Item = Range<usize>> + 'a
+where
+    F: Fn(usize) -> Option<Range<usize>> + 'a,
+{
+    let mut from = Some(0);
+    iter::from_fn(move || {
+        let span = find_at(from?)?;
+        from = if span.is_empty() {
+            haystack[span.end..].chars().next().map(|c| span.end + c.len_utf8())
+        } else {
+            Some(span.end)
+        };
+        Some(span)
+    })
+}
+
+/// A line of input together with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line<'a> {
+    /// 1-based line number.
+    pub number: usize,
+    /// Byte offset of the start of the line within the input.
+    pub offset: usize,
+    /// The line itself, without its terminator.
+    pub text: &'a str,
+}
+
+/// A line selected by a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    pub line: Line<'a>,
+    /// Byte range of the first match within `line.text`; `None` for lines
+    /// selected because they do *not* match (`invert`).
+    pub span: Option<Range<usize>>,
+}
+
+/// Which lines [`search_reader`] selects and what it reports around them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Select the lines that do *not* match.
+    pub invert: bool,
+    /// Lines of context reported before each selected line.
+    pub before_context: usize,
+    /// Lines of context reported after each selected line.
+    pub after_context: usize,
+    /// Stop after this many selected lines (and their trailing context).
+    pub max_count: Option<usize>,
+}
+
+/// What [`search_reader`] reports, in input order.
+#[derive(Debug)]
+pub enum Event<'a> {
+    Match(Match<'a>),
+    Context(Line<'a>),
+    /// Sits between two groups of lines that are not adjacent; only
+    /// reported when context was asked for.
+    Break,
+}
+
+fn select<'a>(matcher: &Matcher, invert: bool, line: Line<'a>) -> Result<Match<'a>, Line<'a>> {
+    let span = matcher.find(line.text);
+    match (span, invert) {
+        (Some(span), false) => Ok(Match { line, span: Some(span) }),
+        (None, true) => Ok(Match { line, span: None }),
+        _ => Err(line),
+    }
+}
+
 // the lifetime of return value is equivalent to the lifetime of contents,
 // because it is a slice refer to contents
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let mut v = Vec::new();
-    for line in contents.lines().into_iter() {
-        if line.contains(query) {
-            v.push(line);
+pub fn search_with<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        let text = raw.strip_suffix('\n').unwrap_or(raw);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let line = Line {
+            number: index + 1,
+            offset,
+            text,
+        };
+        if let Ok(m) = select(matcher, false, line) {
+            matches.push(m);
         }
+        offset += raw.len();
     }
-    v
+    matches
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query_lower = query.to_lowercase();
-    let mut v = Vec::new();
+/// The streaming counterpart of [`search_with`]: reads `reader` and hands
+/// every selected line, plus the requested context around it, to
+/// `on_event` in input order.
+///
+/// Only the `before_context` most recent lines are kept in memory. Literal
+/// queries are looked for in whole buffers of lines at a time, and only the
+/// lines around a match are split out; everything else is read one line at
+/// a time.
+pub fn search_reader<R, F>(matcher: &Matcher, options: &SearchOptions, reader: R, on_event: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    if options.max_count == Some(0) {
+        return Ok(());
+    }
+
+    let mut selector = Selector::new(matcher, options, on_event);
+    if !options.invert && matches!(matcher, Matcher::Literal(_) | Matcher::Literals(_)) {
+        return buffer::search(matcher, reader, buffer::CAPACITY, &mut selector);
+    }
+    crate::io::for_each_line(reader, |number, offset, text| selector.line(Line { number, offset, text }))
+}
+
+/// [`search_reader`] for input that is already in memory, such as a
+/// mapped file: literal queries run over `bytes` where they are, without
+/// copying them into a buffer first.
+pub fn search_slice<F>(matcher: &Matcher, options: &SearchOptions, bytes: &[u8], on_event: F) -> io::Result<()>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    if options.max_count == Some(0) {
+        return Ok(());
+    }
+
+    let mut selector = Selector::new(matcher, options, on_event);
+    if !options.invert && matches!(matcher, Matcher::Literal(_) | Matcher::Literals(_)) {
+        return buffer::search_slice(matcher, bytes, buffer::CAPACITY, &mut selector);
+    }
+    crate::io::for_each_line(bytes, |number, offset, text| selector.line(Line { number, offset, text }))
+}
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query_lower) {
-            v.push(line);
+/// Searches `input` with [`search_slice`] or [`search_reader`], whichever
+/// fits.
+pub fn search_input<F>(matcher: &Matcher, options: &SearchOptions, input: Input, on_event: F) -> io::Result<()>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    match input {
+        Input::Mapped(bytes) => search_slice(matcher, options, bytes, on_event),
+        Input::Stream(reader) => search_reader(matcher, options, reader, on_event),
+    }
+}
+
+/// Decides, line by line, what [`search_reader`] reports.
+struct Selector<'m, F> {
+    matcher: &'m Matcher,
+    options: &'m SearchOptions,
+    on_event: F,
+    before: VecDeque<(usize, usize, String)>,
+    after_remaining: usize,
+    last_reported: Option<usize>,
+    selected: usize,
+}
+
+impl<'m, F> Selector<'m, F>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    fn new(matcher: &'m Matcher, options: &'m SearchOptions, on_event: F) -> Selector<'m, F> {
+        Selector {
+            matcher,
+            options,
+            on_event,
+            before: VecDeque::with_capacity(options.before_context),
+            after_remaining: 0,
+            last_reported: None,
+            selected: 0,
+        }
+    }
+
+    /// Whether the next line has to be shown to [`Selector::line`] even if
+    /// it does not match, because it is owed as trailing context or ends
+    /// the search.
+    fn needs_next_line(&self) -> bool {
+        self.after_remaining > 0 || self.options.max_count == Some(self.selected)
+    }
+
+    /// Takes the next line of input. Lines may only be left out when
+    /// [`Selector::needs_next_line`] is false, they do not match, and more
+    /// than `before_context` lines separate them from the next match.
+    fn line(&mut self, line: Line) -> ControlFlow<()> {
+        let options = self.options;
+        let number = line.number;
+        let offset = line.offset;
+
+        if options.max_count == Some(self.selected) {
+            // the limit is reached, only the trailing context is still owed
+            if self.after_remaining == 0 {
+                return ControlFlow::Break(());
+            }
+            self.after_remaining -= 1;
+            return (self.on_event)(Event::Context(line));
+        }
+
+        let line = match select(self.matcher, options.invert, line) {
+            Ok(m) => {
+                self.selected += 1;
+                let first = self.before.front().map_or(number, |(number, _, _)| *number);
+                if let Some(last) = self.last_reported {
+                    if first > last + 1 && (options.before_context > 0 || options.after_context > 0) {
+                        (self.on_event)(Event::Break)?;
+                    }
+                }
+                for (number, offset, text) in self.before.iter() {
+                    (self.on_event)(Event::Context(Line {
+                        number: *number,
+                        offset: *offset,
+                        text,
+                    }))?;
+                }
+                self.before.clear();
+
+                self.last_reported = Some(number);
+                self.after_remaining = options.after_context;
+                return (self.on_event)(Event::Match(m));
+            }
+            Err(line) => line,
+        };
+
+        if self.after_remaining > 0 {
+            self.after_remaining -= 1;
+            self.last_reported = Some(number);
+            return (self.on_event)(Event::Context(line));
+        }
+
+        if options.before_context > 0 {
+            // recycle the oldest buffer instead of allocating a new one
+            let mut text = if self.before.len() == options.before_context {
+                self.before.pop_front().map(|(_, _, text)| text).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            text.clear();
+            text.push_str(line.text);
+            self.before.push_back((number, offset, text));
         }
+        ControlFlow::Continue(())
     }
-    v
+}
+
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let matches = search_with(&Matcher::Literal(Literal::new(query)), contents);
+    matches.into_iter().map(|m| m.line.text).collect()
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let options = MatchOptions {
+        ignore_case: true,
+        ..MatchOptions::default()
+    };
+    let matcher = Matcher::new(query, options).expect("a literal query always compiles");
+    let matches = search_with(&matcher, contents);
+    matches.into_iter().map(|m| m.line.text).collect()
 }
 
 // pub fn search_case_insensitive_another<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
@@ -35,3 +471,204 @@ pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a st
 //     //    |     returns a value referencing data owned by the current function
 //     search_case_insensitive(&query_lower, &contents_lower)
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENTS: &str = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    fn regex(query: &str, ignore_case: bool) -> Matcher {
+        Matcher::new(query, MatchOptions { regex: true, ignore_case, ..MatchOptions::default() }).unwrap()
+    }
+
+    fn texts(matcher: &Matcher) -> Vec<&'static str> {
+        search_with(matcher, CONTENTS).into_iter().map(|m| m.line.text).collect()
+    }
+
+    fn events(matcher: &Matcher, options: SearchOptions, input: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        search_reader(matcher, &options, io::Cursor::new(input), |event| {
+            events.push(match event {
+                Event::Match(m) => format!("{}:{}", m.line.number, m.line.text),
+                Event::Context(line) => format!("{}-{}", line.number, line.text),
+                Event::Break => "--".to_string(),
+            });
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        events
+    }
+
+    #[test]
+    fn matches_are_structured() {
+        let matches = search_with(&regex("fast|three", false), CONTENTS);
+        assert_eq!(
+            vec![
+                Match {
+                    line: Line {
+                        number: 2,
+                        offset: 6,
+                        text: "safe, fast, productive."
+                    },
+                    span: Some(6..10),
+                },
+                Match {
+                    line: Line {
+                        number: 3,
+                        offset: 30,
+                        text: "Pick three."
+                    },
+                    span: Some(5..10),
+                },
+            ],
+            matches
+        );
+    }
+
+    #[test]
+    fn regex_anchors() {
+        assert_eq!(vec!["Rust:"], texts(&regex("^Rust", false)));
+        assert_eq!(vec!["Rust:"], texts(&regex(r":$", false)));
+    }
+
+    #[test]
+    fn regex_classes_and_alternation() {
+        assert_eq!(vec!["safe, fast, productive."], texts(&regex(r"f[a-z]st", false)));
+        assert_eq!(vec!["Pick three.", "Trust me."], texts(&regex("three|me", false)));
+    }
+
+    #[test]
+    fn regex_composes_with_ignore_case() {
+        assert_eq!(vec!["Rust:", "Trust me."], texts(&regex("^(t)?rust", true)));
+        assert!(texts(&regex("^(t)?rust", false)).is_empty());
+    }
+
+    #[test]
+    fn literal_does_not_interpret_metacharacters() {
+        let matcher = Matcher::new(".", MatchOptions::default()).unwrap();
+        assert_eq!(vec!["safe, fast, productive.", "Pick three.", "Trust me."], texts(&matcher));
+    }
+
+    #[test]
+    fn any_matches_several_queries() {
+        let literals = Matcher::any(&["three", "fast"], MatchOptions::default()).unwrap();
+        assert_eq!(vec!["safe, fast, productive.", "Pick three."], texts(&literals));
+
+        let ignore_case = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        let folded = Matcher::any(&["PICK", "rust"], ignore_case).unwrap();
+        assert_eq!(vec!["Rust:", "Pick three.", "Trust me."], texts(&folded));
+
+        let regexes = Matcher::any(&["^r", r"me\.$"], MatchOptions { regex: true, ..ignore_case }).unwrap();
+        assert_eq!(vec!["Rust:", "Trust me."], texts(&regexes));
+        assert_eq!(r"me\.$", regexes.query_of("Trust me.", &(6..9)));
+
+        let nothing = Matcher::any::<&str>(&[], MatchOptions { regex: true, ..MatchOptions::default() }).unwrap();
+        assert!(texts(&nothing).is_empty());
+
+        // no single line can contain a line break
+        assert!(texts(&Matcher::new("Rust:\nsafe", MatchOptions::default()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let words = MatchOptions {
+            whole_word: true,
+            ..MatchOptions::default()
+        };
+        assert!(texts(&Matcher::new("duct", words).unwrap()).is_empty());
+        assert_eq!(vec!["Rust:"], texts(&Matcher::new("RUST", MatchOptions { ignore_case: true, ..words }).unwrap()));
+        assert_eq!(vec!["Pick three."], texts(&Matcher::any(&["three", "thr"], words).unwrap()));
+        assert_eq!(vec!["Trust me."], texts(&Matcher::new("m[a-z]", MatchOptions { regex: true, ..words }).unwrap()));
+
+        let lines = MatchOptions {
+            whole_line: true,
+            ..words
+        };
+        assert_eq!(vec!["Pick three."], texts(&Matcher::new("Pick three.", lines).unwrap()));
+        assert!(texts(&Matcher::new("three", lines).unwrap()).is_empty());
+        let regex = MatchOptions {
+            regex: true,
+            ignore_case: true,
+            ..lines
+        };
+        assert_eq!(vec!["Rust:"], texts(&Matcher::new("r.*:", regex).unwrap()));
+    }
+
+    #[test]
+    fn search_reader_agrees_with_search_with() {
+        let matcher = regex("duct|three", false);
+        let events = events(&matcher, SearchOptions::default(), CONTENTS);
+        assert_eq!(vec!["2:safe, fast, productive.", "3:Pick three."], events);
+        assert_eq!(vec!["safe, fast, productive.", "Pick three."], texts(&matcher));
+    }
+
+    #[test]
+    fn search_reader_reports_context_and_breaks() {
+        let input = "a\nmatch 1\nb\nc\nd\ne\nmatch 2\nf\nmatch 3\ng\n";
+        let matcher = regex("match", false);
+        let options = SearchOptions {
+            before_context: 1,
+            after_context: 1,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            vec!["1-a", "2:match 1", "3-b", "--", "6-e", "7:match 2", "8-f", "9:match 3", "10-g"],
+            events(&matcher, options, input)
+        );
+
+        let options = SearchOptions {
+            before_context: 2,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            vec!["1-a", "2:match 1", "--", "5-d", "6-e", "7:match 2", "8-f", "9:match 3"],
+            events(&matcher, options, input)
+        );
+    }
+
+    #[test]
+    fn search_reader_inverts_and_stops_at_max_count() {
+        let input = "a\nmatch 1\nb\nmatch 2\nc\nmatch 3\n";
+        let matcher = regex("match", false);
+        let options = SearchOptions {
+            invert: true,
+            max_count: Some(2),
+            ..SearchOptions::default()
+        };
+        assert_eq!(vec!["1:a", "3:b"], events(&matcher, options, input));
+
+        // trailing context is still printed, even when it would match
+        let options = SearchOptions {
+            after_context: 2,
+            max_count: Some(1),
+            ..SearchOptions::default()
+        };
+        assert_eq!(vec!["2:match 1", "3-b", "4-match 2"], events(&matcher, options, input));
+
+        let options = SearchOptions {
+            max_count: Some(0),
+            ..SearchOptions::default()
+        };
+        assert!(events(&matcher, options, input).is_empty());
+    }
+
+    #[test]
+    fn find_iter_reports_every_match() {
+        let literal = Matcher::new("a", MatchOptions::default()).unwrap();
+        assert_eq!(vec![1..2, 7..8], literal.find_iter("safe, fast").collect::<Vec<_>>());
+        assert_eq!(vec![0..4, 5..9], regex("f.st|safe", false).find_iter("safe fast").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(Matcher::new("(", MatchOptions { regex: true, ..MatchOptions::default() }).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::iter;
+use std::ops::Range;
+
+use memchr::{memchr, memchr_iter, memrchr};
+
+use super::{Line, Matcher};
+
+/// The lines a match found by a multiline search touches, from the start
+/// of the line it starts on to the end of the line it ends on. Matches that
+/// share a line are merged into one region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region<'a> {
+    /// The region as one line: the number of its first line, its offset,
+    /// and the text of all its lines, joined by their terminators.
+    pub line: Line<'a>,
+    /// The number of the last line of the region.
+    pub last_number: usize,
+    /// Byte range of the first match within `line.text`.
+    pub span: Range<usize>,
+}
+
+/// Every region of `text` that `matcher`, built with `multiline`, matches,
+/// in order.
+pub fn regions<'a>(matcher: &'a Matcher, text: &'a str) -> impl Iterator<Item = Region<'a>> + 'a {
+    let bytes = text.as_bytes();
+    // a match right at the end of input that ends with a newline would sit
+    // on a line that is not there
+    let ghost = |span: &Range<usize>| !text.is_empty() && span.start == text.len() && text.ends_with('\n');
+    let mut matches = matcher.find_iter(text).filter(move |span| !ghost(span)).peekable();
+    let mut number = 1;
+    let mut numbered = 0;
+
+    iter::from_fn(move || {
+        let first = matches.next()?;
+        let start = memrchr(b'\n', &bytes[..first.start]).map_or(0, |newline| newline + 1);
+        let mut end = line_end(bytes, &first);
+        while let Some(next) = matches.next_if(|next| next.start <= end) {
+            end = end.max(line_end(bytes, &next));
+        }
+
+        number += memchr_iter(b'\n', &bytes[numbered..start]).count();
+        numbered = start;
+        let region = &text[start..end];
+        let region = region.strip_suffix('\r').unwrap_or(region);
+        Some(Region {
+            line: Line {
+                number,
+                offset: start,
+                text: region,
+            },
+            last_number: number + memchr_iter(b'\n', region.as_bytes()).count(),
+            span: first.start - start..first.end - start,
+        })
+    })
+}
+
+/// Where the line on which `span` ends does, not counting its terminator.
+fn line_end(bytes: &[u8], span: &Range<usize>) -> usize {
+    // a match that takes in a line break ends on the line that it breaks
+    let last = if span.is_empty() { span.start } else { span.end - 1 };
+    if bytes.get(last) == Some(&b'\n') {
+        return last;
+    }
+    memchr(b'\n', &bytes[last..]).map_or(bytes.len(), |newline| last + newline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MatchOptions;
+
+    fn regions_of(patterns: &[&str], regex: bool, text: &str) -> Vec<(usize, usize, String)> {
+        let options = MatchOptions {
+            regex,
+            multiline: true,
+            ..MatchOptions::default()
+        };
+        let matcher = Matcher::any(patterns, options).unwrap();
+        regions(&matcher, text)
+            .map(|region| (region.line.number, region.last_number, region.line.text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_span_lines() {
+        let text = "fn main(\n    x: u32,\n) {\n}\nfn other() {}\n";
+        assert_eq!(
+            vec![(1, 3, "fn main(\n    x: u32,\n) {".to_string()), (5, 5, "fn other() {}".to_string())],
+            regions_of(&[r"fn \w+\([^)]*\)"], true, text)
+        );
+        assert_eq!(vec![(2, 3, "b\r\nc".to_string())], regions_of(&["b\r\nc"], false, "a\r\nb\r\nc\r\nd"));
+    }
+
+    #[test]
+    fn matches_on_the_same_lines_are_merged() {
+        let text = "a1 a2\nb\na3\nx\n";
+        assert_eq!(vec![(1, 1, "a1 a2".to_string()), (3, 3, "a3".to_string())], regions_of(&["a"], false, text));
+        assert_eq!(vec![(1, 2, "a1 a2\nb".to_string())], regions_of(&["a1", "a2\nb"], false, text));
+        // line anchors work on every line, but not past the last one
+        assert_eq!(vec![(3, 3, "a3".to_string())], regions_of(&["^a3$"], true, text));
+        assert_eq!(4, regions_of(&["^"], true, text).len());
+    }
+}
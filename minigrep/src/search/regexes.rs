@@ -0,0 +1,180 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::ast::parse::Parser;
+use regex_syntax::ast::print::Printer;
+use regex_syntax::ast::{Ast, GroupKind};
+
+use super::MatchOptions;
+
+/// Several regular expressions combined into one alternation, so a line is
+/// still searched once however many there are. A [`Bound`](super::Bound)
+/// is applied to each of them.
+///
+/// Queries are escaped first unless `regex` is set. At the leftmost
+/// position where any of them matches, the one given first wins, as with
+/// `|`. The expressions are also kept one by one, to tell which of them
+/// produced a match.
+#[derive(Debug, Clone)]
+pub struct Regexes {
+    queries: Vec<String>,
+    combined: Regex,
+    each: Vec<Regex>,
+}
+
+impl Regexes {
+    pub fn new<S: AsRef<str>>(queries: &[S], options: MatchOptions) -> Result<Regexes, regex::Error> {
+        let queries: Vec<String> = queries.iter().map(|query| query.as_ref().to_string()).collect();
+        let build = |query: &str| {
+            let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+            let pattern = match options.bound() {
+                Some(bound) => bound.wrap(&pattern),
+                None => pattern,
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(options.ignore_case)
+                .multi_line(options.multiline)
+                .crlf(options.multiline)
+                .build()
+        };
+        let each = queries.iter().map(|query| build(query)).collect::<Result<Vec<_>, _>>()?;
+        let alternation: Vec<String> = each.iter().map(|re| format!("(?:{})", unnamed(re.as_str()))).collect();
+        let combined = RegexBuilder::new(&alternation.join("|"))
+            .case_insensitive(options.ignore_case)
+            .multi_line(options.multiline)
+            .crlf(options.multiline)
+            .build()?;
+        Ok(Regexes {
+            queries,
+            combined,
+            each,
+        })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.combined.is_match(line)
+    }
+
+    pub fn find_at(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        self.combined.find_at(line, from).map(|m| m.range())
+    }
+
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.combined.find_iter(line).map(|m| m.range())
+    }
+
+    /// The expression that produced `span`, a match found in `line`: the
+    /// first one that matches at the same position.
+    pub fn query_of(&self, line: &str, span: &Range<usize>) -> &str {
+        self.index_of(line, span).map_or("", |index| &self.queries[index])
+    }
+
+    /// The compiled expression that produced `span`, a match found in
+    /// `line`, with its own capture groups.
+    pub fn regex_of(&self, line: &str, span: &Range<usize>) -> &Regex {
+        self.index_of(line, span).map_or(&self.combined, |index| &self.each[index])
+    }
+
+    fn index_of(&self, line: &str, span: &Range<usize>) -> Option<usize> {
+        let matched = |re: &Regex| re.find_at(line, span.start).is_some_and(|m| m.range() == *span);
+        self.each.iter().position(matched)
+    }
+}
+
+/// `pattern`, a valid expression, with its named groups turned into plain
+/// ones, so that expressions which use the same name can be joined. Only
+/// the expressions kept one by one are asked for captures.
+fn unnamed(pattern: &str) -> String {
+    fn strip(ast: &mut Ast) {
+        match ast {
+            Ast::Group(group) => {
+                if let GroupKind::CaptureName { .. } = group.kind {
+                    group.kind = GroupKind::CaptureIndex(0);
+                }
+                strip(&mut group.ast);
+            }
+            Ast::Repetition(repetition) => strip(&mut repetition.ast),
+            Ast::Alternation(alternation) => alternation.asts.iter_mut().for_each(strip),
+            Ast::Concat(concat) => concat.asts.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+
+    let mut ast = match Parser::new().parse(pattern) {
+        Ok(ast) => ast,
+        Err(_) => return pattern.to_string(),
+    };
+    strip(&mut ast);
+    let mut unnamed = String::new();
+    match Printer::new().print(&ast, &mut unnamed) {
+        Ok(()) => unnamed,
+        Err(_) => pattern.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_which_expression_matched() {
+        let options = MatchOptions {
+            regex: true,
+            ..MatchOptions::default()
+        };
+        let regexes = Regexes::new(&["a+", "[0-9]+", "(a|b)c"], options).unwrap();
+        let line = "bc 42 aaa";
+        let spans: Vec<_> = regexes.find_iter(line).collect();
+        assert_eq!(vec![0..2, 3..5, 6..9], spans);
+        let queries: Vec<_> = spans.iter().map(|span| regexes.query_of(line, span)).collect();
+        assert_eq!(vec!["(a|b)c", "[0-9]+", "a+"], queries);
+    }
+
+    #[test]
+    fn the_first_expression_wins_at_the_same_position() {
+        let options = MatchOptions {
+            regex: true,
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        let regexes = Regexes::new(&["fo", "foo"], options).unwrap();
+        assert_eq!(Some(0..2), regexes.find_at("FOO", 0));
+        assert_eq!("fo", regexes.query_of("FOO", &(0..2)));
+        assert!(Regexes::new(&["ok", "("], options).is_err());
+    }
+
+    #[test]
+    fn bounds_apply_to_every_expression() {
+        let options = MatchOptions {
+            regex: true,
+            whole_word: true,
+            ..MatchOptions::default()
+        };
+        let regexes = Regexes::new(&["fo+", "ba[rz]"], options).unwrap();
+        assert_eq!(vec![5..8, 14..17], regexes.find_iter("food foo bars baz").collect::<Vec<_>>());
+        assert_eq!("ba[rz]", regexes.query_of("food foo bars baz", &(14..17)));
+    }
+
+    #[test]
+    fn expressions_may_share_group_names() {
+        let options = MatchOptions {
+            regex: true,
+            ..MatchOptions::default()
+        };
+        let regexes = Regexes::new(&[r"(?P<n>\d+)px", r"(?<n>\d+)em"], options).unwrap();
+        let line = "12px 3em";
+        assert_eq!(vec![0..4, 5..8], regexes.find_iter(line).collect::<Vec<_>>());
+        let sizes: Vec<_> = regexes
+            .find_iter(line)
+            .map(|span| regexes.regex_of(line, &span).captures(&line[span.start..]).unwrap()["n"].to_string())
+            .collect();
+        assert_eq!(vec!["12", "3"], sizes);
+    }
+
+    #[test]
+    fn literal_queries_are_escaped() {
+        let regexes = Regexes::new(&["a.c", "("], MatchOptions::default()).unwrap();
+        assert!(!regexes.is_match("abc"));
+        assert_eq!(vec![2..5, 6..7], regexes.find_iter("x a.c (").collect::<Vec<_>>());
+    }
+}
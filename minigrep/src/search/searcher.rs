@@ -0,0 +1,172 @@
+use std::io::{self, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use super::{Event, Line, Match, Matcher, SearchOptions};
+use crate::io::{Input, MmapChoice};
+
+/// Receives what a [`Searcher`] finds, in input order.
+///
+/// Each method can stop the search by returning `ControlFlow::Break`, or
+/// fail it with an error, which the search then returns. Only
+/// [`Sink::matched`] has to be written; context is ignored by default.
+pub trait Sink {
+    /// A line the search selected.
+    fn matched(&mut self, m: &Match) -> io::Result<ControlFlow<()>>;
+
+    /// A line of context around a selected one.
+    fn context(&mut self, _line: &Line) -> io::Result<ControlFlow<()>> {
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Sits between two groups of lines that are not adjacent; only comes
+    /// up when context was asked for.
+    fn context_break(&mut self) -> io::Result<ControlFlow<()>> {
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// A [`Matcher`] together with the [`SearchOptions`] to search with, ready
+/// to run over any number of inputs and hand what it finds to a [`Sink`].
+///
+/// This is how minigrep's own output is produced, so an embedding program
+/// sees exactly the lines the command line would print, without capturing
+/// standard output. A `Searcher` can be shared between threads.
+#[derive(Debug)]
+pub struct Searcher {
+    matcher: Matcher,
+    options: SearchOptions,
+}
+
+impl Searcher {
+    pub fn new(matcher: Matcher, options: SearchOptions) -> Searcher {
+        Searcher { matcher, options }
+    }
+
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    /// Searches everything `reader` has to give, which is read a buffer at
+    /// a time.
+    pub fn search_reader<R: Read, S: Sink>(&self, reader: R, sink: &mut S) -> io::Result<()> {
+        let mut failed = Ok(());
+        super::search_reader(&self.matcher, &self.options, BufReader::new(reader), forward(sink, &mut failed))?;
+        failed
+    }
+
+    /// Searches `bytes` where they are.
+    pub fn search_slice<S: Sink>(&self, bytes: &[u8], sink: &mut S) -> io::Result<()> {
+        let mut failed = Ok(());
+        super::search_slice(&self.matcher, &self.options, bytes, forward(sink, &mut failed))?;
+        failed
+    }
+
+    /// Searches the file at `path`, which is mapped into memory if it is
+    /// large enough for that to pay off.
+    pub fn search_path<S: Sink>(&self, path: &Path, sink: &mut S) -> io::Result<()> {
+        match crate::io::map(path, MmapChoice::Auto)? {
+            Some(map) => self.search_slice(&map, sink),
+            None => self.search_input(Input::Stream(crate::io::open(path)?), sink),
+        }
+    }
+
+    /// Searches a file as opened by minigrep itself, see [`Input`].
+    pub fn search_input<S: Sink>(&self, input: Input, sink: &mut S) -> io::Result<()> {
+        let mut failed = Ok(());
+        super::search_input(&self.matcher, &self.options, input, forward(sink, &mut failed))?;
+        failed
+    }
+}
+
+/// Hands events to `sink`, keeping the first error it returns in `failed`.
+fn forward<'s, S: Sink>(sink: &'s mut S, failed: &'s mut io::Result<()>) -> impl FnMut(Event) -> ControlFlow<()> + 's {
+    move |event| {
+        let flow = match &event {
+            Event::Match(m) => sink.matched(m),
+            Event::Context(line) => sink.context(line),
+            Event::Break => sink.context_break(),
+        };
+        flow.unwrap_or_else(|e| {
+            *failed = Err(e);
+            ControlFlow::Break(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MatchOptions;
+
+    /// Renders everything, and gives up after `limit` selected lines.
+    struct Transcript {
+        lines: Vec<String>,
+        matched: usize,
+        limit: usize,
+    }
+
+    impl Sink for Transcript {
+        fn matched(&mut self, m: &Match) -> io::Result<ControlFlow<()>> {
+            self.lines.push(format!("{}:{}", m.line.number, m.line.text));
+            self.matched += 1;
+            if self.matched == self.limit {
+                return Err(io::Error::other("enough"));
+            }
+            Ok(ControlFlow::Continue(()))
+        }
+
+        fn context(&mut self, line: &Line) -> io::Result<ControlFlow<()>> {
+            self.lines.push(format!("{}-{}", line.number, line.text));
+            Ok(ControlFlow::Continue(()))
+        }
+
+        fn context_break(&mut self) -> io::Result<ControlFlow<()>> {
+            self.lines.push("--".to_string());
+            Ok(ControlFlow::Continue(()))
+        }
+    }
+
+    const INPUT: &str = "one\ntwo\nthree\nfour\nfive\nsix\n";
+
+    #[test]
+    fn sinks_see_matches_and_context_in_order() {
+        let options = SearchOptions {
+            after_context: 1,
+            ..SearchOptions::default()
+        };
+        let searcher = Searcher::new(Matcher::new("o", MatchOptions::default()).unwrap(), options);
+        let mut transcript = Transcript {
+            lines: Vec::new(),
+            matched: 0,
+            limit: usize::MAX,
+        };
+        searcher.search_reader(INPUT.as_bytes(), &mut transcript).unwrap();
+        assert_eq!(vec!["1:one", "2:two", "3-three", "4:four", "5-five"], transcript.lines);
+
+        let mut sliced = Transcript {
+            lines: Vec::new(),
+            matched: 0,
+            limit: usize::MAX,
+        };
+        searcher.search_slice(INPUT.as_bytes(), &mut sliced).unwrap();
+        assert_eq!(transcript.lines, sliced.lines);
+    }
+
+    #[test]
+    fn sink_errors_stop_the_search() {
+        let searcher = Searcher::new(Matcher::new("i", MatchOptions::default()).unwrap(), SearchOptions::default());
+        let mut transcript = Transcript {
+            lines: Vec::new(),
+            matched: 0,
+            limit: 1,
+        };
+        let err = searcher.search_reader(INPUT.as_bytes(), &mut transcript).unwrap_err();
+        assert_eq!("enough", err.to_string());
+        assert_eq!(vec!["5:five"], transcript.lines);
+    }
+}
@@ -0,0 +1,237 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::types::{Types, TypesBuilder};
+use ignore::WalkBuilder;
+
+use crate::index;
+use crate::io::STDIN;
+
+/// Which files a directory walk skips.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Also search hidden files and directories (names starting with `.`).
+    pub hidden: bool,
+    /// Ignore `.gitignore`, `.ignore` and git's exclude files.
+    pub no_ignore: bool,
+    /// Globs, relative to the current directory, a file has to match; a
+    /// glob starting with `!` excludes the files it matches instead.
+    pub globs: Vec<String>,
+    /// File types to search, such as `rust` or `md`.
+    pub types: Vec<String>,
+    /// File types to skip.
+    pub types_not: Vec<String>,
+}
+
+/// Expands every path into the files below it, in a stable (sorted) order.
+/// [`STDIN`] is passed through untouched.
+///
+/// Directories are walked recursively, honouring ignore files and skipping
+/// hidden entries unless `options` says otherwise; globs and types only
+/// filter what is found while walking, never a path named explicitly.
+/// Symbolic links named on the command line are followed, but links found
+/// while walking are not, so a link pointing back up the tree cannot make
+/// the walk loop forever. Index files, which `minigrep index build`
+/// writes, are skipped too. Paths that cannot be read are reported through
+/// `on_error` and skipped; an invalid glob or unknown type is an error.
+pub fn files<F>(paths: &[String], options: &WalkOptions, mut on_error: F) -> Result<Vec<PathBuf>, ignore::Error>
+where
+    F: FnMut(&Path, io::Error),
+{
+    let overrides = overrides(&options.globs)?;
+    let types = types(options)?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path == Path::new(STDIN) {
+            files.push(path.to_path_buf());
+            continue;
+        }
+
+        let walk = WalkBuilder::new(path)
+            .standard_filters(!options.no_ignore)
+            .hidden(!options.hidden)
+            .require_git(false)
+            .overrides(overrides.clone())
+            .types(types.clone())
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+
+        for entry in walk {
+            match entry {
+                // minigrep's own index files are not searched, unless named
+                Ok(entry) if entry.depth() > 0 && index::is_index_file(entry.file_name()) => {}
+                Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_file()) => {
+                    files.push(entry.into_path());
+                }
+                Ok(_) => {}
+                Err(e) => report(path, e, &mut on_error),
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn overrides(globs: &[String]) -> Result<Override, ignore::Error> {
+    let mut builder = OverrideBuilder::new(".");
+    for glob in globs {
+        builder.add(glob)?;
+    }
+    builder.build()
+}
+
+fn types(options: &WalkOptions) -> Result<Types, ignore::Error> {
+    let mut builder = TypesBuilder::new();
+    builder.add_defaults();
+    for name in &options.types {
+        builder.select(name);
+    }
+    for name in &options.types_not {
+        builder.negate(name);
+    }
+    builder.build()
+}
+
+/// Unwraps the path and I/O error out of a walk error, falling back to the
+/// root being walked.
+fn report<F>(root: &Path, err: ignore::Error, on_error: &mut F)
+where
+    F: FnMut(&Path, io::Error),
+{
+    match err {
+        ignore::Error::WithPath { path, err } => report(&path, *err, on_error),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            report(root, *err, on_error)
+        }
+        err => {
+            let message = err.to_string();
+            let err = err.into_io_error().map_or_else(|| io::Error::other(message), innermost);
+            on_error(root, err)
+        }
+    }
+}
+
+/// The I/O error at the bottom of `err`. Errors from walking a directory
+/// come wrapped in another I/O error whose message names the path again,
+/// which the caller already reports.
+fn innermost(err: io::Error) -> io::Error {
+    let inner = err.get_ref().and_then(|inner| inner.source()).and_then(|source| source.downcast_ref::<io::Error>());
+    match inner {
+        Some(inner) => match inner.raw_os_error() {
+            Some(code) => io::Error::from_raw_os_error(code),
+            None => io::Error::new(inner.kind(), inner.to_string()),
+        },
+        None => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// Creates `files` under a fresh temporary directory.
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("minigrep-walk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let (path, contents) = file.split_once('=').unwrap_or((file, ""));
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    fn walk(root: &Path, options: &WalkOptions) -> Vec<String> {
+        let found = files(&[root.display().to_string()], options, |path, e| panic!("{}: {}", path.display(), e)).unwrap();
+        found
+            .iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn walks_directories_in_sorted_order() {
+        let root = tree("sorted", &["c.txt", "a.txt", "b/nested/d.txt"]);
+
+        let mut errors = Vec::new();
+        let paths = [root.display().to_string(), "does-not-exist".to_string()];
+        let found = files(&paths, &WalkOptions::default(), |path, e| errors.push((path.to_path_buf(), e))).unwrap();
+        let found: Vec<_> = found.iter().map(|p| p.strip_prefix(&root).unwrap().to_path_buf()).collect();
+
+        assert_eq!(vec![PathBuf::from("a.txt"), PathBuf::from("b/nested/d.txt"), PathBuf::from("c.txt")], found);
+        assert_eq!(1, errors.len());
+        // the path is not repeated in the message
+        let (path, err) = &errors[0];
+        assert_eq!((Path::new("does-not-exist"), io::ErrorKind::NotFound), (path.as_path(), err.kind()));
+        assert!(!err.to_string().contains("does-not-exist"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn honours_ignore_files_and_hidden_entries() {
+        let root = tree(
+            "ignore",
+            &[
+                ".gitignore=target/\n*.log\n",
+                ".ignore=skip.rs\n",
+                ".hidden.rs",
+                ".minigrep-index",
+                ".minigrep-index-42",
+                "main.rs",
+                "skip.rs",
+                "run.log",
+                "target/out.rs",
+            ],
+        );
+
+        assert_eq!(vec!["main.rs"], walk(&root, &WalkOptions::default()));
+
+        let hidden = WalkOptions {
+            hidden: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec![".gitignore", ".hidden.rs", ".ignore", "main.rs"], walk(&root, &hidden));
+
+        let no_ignore = WalkOptions {
+            no_ignore: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec!["main.rs", "run.log", "skip.rs", "target/out.rs"], walk(&root, &no_ignore));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn filters_by_glob_and_type() {
+        let root = tree("filters", &["a.rs", "b.md", "c.txt", "tests/d.rs"]);
+
+        let rust = WalkOptions {
+            types: vec!["rust".to_string()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec!["a.rs", "tests/d.rs"], walk(&root, &rust));
+
+        let not_rust = WalkOptions {
+            types_not: vec!["rust".to_string()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec!["b.md", "c.txt"], walk(&root, &not_rust));
+
+        let globs = WalkOptions {
+            globs: vec!["*.rs".to_string(), "!**/tests/**".to_string()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec!["a.rs"], walk(&root, &globs));
+
+        let unknown = WalkOptions {
+            types: vec!["no-such-type".to_string()],
+            ..WalkOptions::default()
+        };
+        assert!(files(&[root.display().to_string()], &unknown, |_, _| {}).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}